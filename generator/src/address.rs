@@ -0,0 +1,488 @@
+use std::{collections::HashMap, f64::consts::PI};
+
+use fastlem::models::surface::sites::Site2D;
+use rand::seq::SliceRandom;
+use street_engine::core::geometry::site::Site;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    map::{random::RandomStream, road::RoadNetwork, Map},
+    params::GenerationParams,
+    placename::{NameConfig, NameGenerator},
+    types::{to_map_sites, MapSite, Name},
+};
+
+/// Kind of a named street.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum StreetKind {
+    /// 国道, a main road numbered by the nation.
+    National = 0,
+    /// 道道, a main road numbered by Hokkaido.
+    Prefectural = 1,
+    /// 〜通, a local road in a town.
+    Avenue = 2,
+    /// 〜線, a local road outside the towns.
+    Line = 3,
+}
+
+impl StreetKind {
+    pub(crate) fn from_code(code: u8) -> Option<Self> {
+        [
+            StreetKind::National,
+            StreetKind::Prefectural,
+            StreetKind::Avenue,
+            StreetKind::Line,
+        ]
+        .into_iter()
+        .find(|kind| *kind as u8 == code)
+    }
+}
+
+/// A named chain of roads.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Street {
+    kind: StreetKind,
+    name: Name,
+    sites: Vec<Site>,
+}
+
+#[wasm_bindgen]
+impl Street {
+    pub fn kind(&self) -> StreetKind {
+        self.kind
+    }
+
+    /// Full name of the street, e.g. `国道230号` or `〜通`.
+    pub fn name(&self) -> Name {
+        self.name.clone()
+    }
+
+    pub fn sites(&self) -> Vec<MapSite> {
+        to_map_sites(&self.sites)
+    }
+}
+
+/// Address of a place in the Hokkaido style, such as `中央北3条西2丁目`.
+///
+/// Blocks are numbered from the center of the district along the grid of the town:
+/// 条 to the north (北) and the south (南), and 丁目 to the east (東) and the west (西).
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Address {
+    district: Name,
+    jo: u32,
+    north: bool,
+    chome: u32,
+    east: bool,
+    street: Option<Name>,
+}
+
+#[wasm_bindgen]
+impl Address {
+    pub fn district(&self) -> Name {
+        self.district.clone()
+    }
+
+    pub fn jo(&self) -> u32 {
+        self.jo
+    }
+
+    /// `北` or `南`.
+    pub fn jo_direction(&self) -> String {
+        if self.north { "北" } else { "南" }.to_string()
+    }
+
+    pub fn chome(&self) -> u32 {
+        self.chome
+    }
+
+    /// `東` or `西`.
+    pub fn chome_direction(&self) -> String {
+        if self.east { "東" } else { "西" }.to_string()
+    }
+
+    /// The nearest named street, if any is close.
+    pub fn street(&self) -> Option<Name> {
+        self.street.clone()
+    }
+
+    /// The address without the city, e.g. `中央北3条西2丁目`.
+    pub fn text(&self) -> String {
+        format!(
+            "{}{}{}条{}{}丁目",
+            self.district.name(),
+            self.jo_direction(),
+            self.jo,
+            self.chome_direction(),
+            self.chome
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct NamedStreet {
+    /// Indices of the nodes of the road network in order.
+    pub nodes: Vec<usize>,
+    pub kind: StreetKind,
+    pub name: Name,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct District {
+    pub center: Site,
+    pub name: Name,
+}
+
+/// Smallest cell of the grid to look up addresses, which keeps long streets in a few cells.
+const GRID_CELL_SIZE_MIN: f64 = 0.5;
+
+/// Named streets and districts of a map, to look up addresses.
+#[derive(Debug, Clone)]
+pub(crate) struct Addressing {
+    pub streets: Vec<NamedStreet>,
+    pub districts: Vec<District>,
+    pub block_size: f64,
+    pub road_distance_max: f64,
+    grid: RoadGrid,
+}
+
+impl Addressing {
+    pub fn new(
+        network: &RoadNetwork,
+        streets: Vec<NamedStreet>,
+        districts: Vec<District>,
+        block_size: f64,
+        road_distance_max: f64,
+    ) -> Self {
+        let grid = RoadGrid::new(network, &streets, road_distance_max);
+        Self {
+            streets,
+            districts,
+            block_size,
+            road_distance_max,
+            grid,
+        }
+    }
+
+    /// Name the streets and the districts of the map.
+    ///
+    /// The districts around the settlements take their names, and the one of the main town is `中央`.
+    /// Districts and streets whose names fail to generate are numbered instead.
+    pub fn generate(
+        map: &Map,
+        settlement_names: &[Name],
+        namegen: &mut NameGenerator,
+        params: &GenerationParams,
+        seed: u32,
+    ) -> Self {
+        let name_config = |target_name_length| NameConfig {
+            target_name_length,
+            cmp_samples: params.name_cmp_samples,
+        };
+
+        let mut districts = map
+            .settlements
+            .iter()
+            .zip(settlement_names)
+            .enumerate()
+            .map(|(i, (settlement, name))| District {
+                center: settlement.site,
+                name: if i == 0 {
+                    Name::from_tuple(("中央".to_string(), "chuo".to_string()))
+                } else {
+                    name.clone()
+                },
+            })
+            .collect::<Vec<_>>();
+        // the nodes are in the order of growth, so the districts spread from the settlements
+        for node in map.network.nodes() {
+            if node.stage.as_num() == 0
+                || districts
+                    .iter()
+                    .any(|district| district.center.distance(&node.site) < params.district_size)
+            {
+                continue;
+            }
+            let name = namegen
+                .generate(name_config(params.district_name_length))
+                .unwrap_or_else(|_| {
+                    let number = districts.len() + 1;
+                    (format!("第{}地区", number), format!("dai {} chiku", number))
+                });
+            districts.push(District {
+                center: node.site,
+                name: Name::from_tuple(name),
+            });
+        }
+
+        let nodes = map.network.nodes();
+        let length = |street: &[usize]| {
+            street
+                .windows(2)
+                .map(|pair| nodes[pair[0]].site.distance(&nodes[pair[1]].site))
+                .sum::<f64>()
+        };
+        let mut streets = map
+            .network
+            .streets(PI / 4.0)
+            .into_iter()
+            .map(|street| (length(&street), street))
+            .filter(|(length, _)| *length >= params.street_length_min)
+            .collect::<Vec<_>>();
+        // main roads are numbered from the longest
+        streets.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        // route numbers in use in Hokkaido, drawn without replacement
+        let mut rnd = RandomStream::Streets.rng(seed);
+        let mut national_numbers = (227..=453).collect::<Vec<u32>>();
+        let mut prefectural_numbers = (1..=1200).collect::<Vec<u32>>();
+        national_numbers.shuffle(&mut rnd);
+        prefectural_numbers.shuffle(&mut rnd);
+
+        let mut named_streets = Vec::with_capacity(streets.len());
+        for (length, street) in streets {
+            let is_main =
+                nodes[street[0]].stage.as_num() == 0 && nodes[street[1]].stage.as_num() == 0;
+            let (kind, name) = if is_main {
+                let is_longest = !named_streets
+                    .iter()
+                    .any(|street: &NamedStreet| street.kind == StreetKind::National);
+                let national = (is_longest || length >= params.national_road_length_min)
+                    .then(|| national_numbers.pop())
+                    .flatten();
+                if let Some(number) = national {
+                    (
+                        StreetKind::National,
+                        (format!("国道{}号", number), format!("kokudo {} go", number)),
+                    )
+                } else if let Some(number) = prefectural_numbers.pop() {
+                    (
+                        StreetKind::Prefectural,
+                        (format!("道道{}号", number), format!("dodo {} go", number)),
+                    )
+                } else {
+                    continue;
+                }
+            } else {
+                let middle = nodes[street[street.len() / 2]].site;
+                let density = map.population_density_at(middle.x, middle.y);
+                let name = namegen
+                    .generate(name_config(params.street_name_length))
+                    .ok();
+                // numbered like the grid roads of the farmland, e.g. `3線`, if the name fails
+                let number = named_streets.len() + 1;
+                let is_avenue = density >= params.avenue_density_min;
+                let (name, reading) = name.unwrap_or_else(|| {
+                    if is_avenue {
+                        (format!("{}番", number), format!("{} ban", number))
+                    } else {
+                        (number.to_string(), number.to_string())
+                    }
+                });
+                if is_avenue {
+                    (
+                        StreetKind::Avenue,
+                        (format!("{}通", name), format!("{} dori", reading)),
+                    )
+                } else {
+                    (
+                        StreetKind::Line,
+                        (format!("{}線", name), format!("{} sen", reading)),
+                    )
+                }
+            };
+            named_streets.push(NamedStreet {
+                nodes: street,
+                kind,
+                name: Name::from_tuple(name),
+            });
+        }
+
+        Self::new(
+            &map.network,
+            named_streets,
+            districts,
+            params.block_size,
+            params.address_road_distance_max,
+        )
+    }
+
+    pub fn streets(&self, map: &Map) -> Vec<Street> {
+        let nodes = map.network.nodes();
+        self.streets
+            .iter()
+            .map(|street| Street {
+                kind: street.kind,
+                name: street.name.clone(),
+                sites: street.nodes.iter().map(|&i| nodes[i].site).collect(),
+            })
+            .collect()
+    }
+
+    /// Address of the given point, or `None` in the sea or away from the roads.
+    pub fn address_at(&self, map: &Map, x: f64, y: f64) -> Option<Address> {
+        let site = Site { x, y };
+        let elevation = map.terrain.get_elevation(&Site2D { x, y })?;
+        if elevation < map.sea_level {
+            return None;
+        }
+        let nodes = map.network.nodes();
+        if !self
+            .grid
+            .nodes_around(site, self.road_distance_max)
+            .any(|i| nodes[i].site.distance(&site) <= self.road_distance_max)
+        {
+            return None;
+        }
+        let district = self.districts.iter().min_by(|a, b| {
+            a.center
+                .distance(&site)
+                .total_cmp(&b.center.distance(&site))
+        })?;
+
+        // the grid of the town follows the first road from the origin
+        let (sin, cos) = map.initial_angle.sin_cos();
+        let east = if cos.abs() >= sin.abs() {
+            (cos, sin)
+        } else {
+            (-sin, cos)
+        };
+        let east = if east.0 < 0.0 {
+            (-east.0, -east.1)
+        } else {
+            east
+        };
+        // the north is the negative y direction
+        let north = (east.1, -east.0);
+        let (dx, dy) = (x - district.center.x, y - district.center.y);
+        let (eastward, northward) = (dx * east.0 + dy * east.1, dx * north.0 + dy * north.1);
+        let block = |distance: f64| (distance.abs() / self.block_size) as u32 + 1;
+
+        let street = self
+            .grid
+            .segments_around(site, self.road_distance_max)
+            .map(|(street, i)| {
+                let street = &self.streets[street];
+                (
+                    street,
+                    segment_distance(
+                        site,
+                        nodes[street.nodes[i]].site,
+                        nodes[street.nodes[i + 1]].site,
+                    ),
+                )
+            })
+            .filter(|(_, distance)| *distance <= self.road_distance_max)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(street, _)| street.name.clone());
+
+        Some(Address {
+            district: district.name.clone(),
+            jo: block(northward),
+            north: northward >= 0.0,
+            chome: block(eastward),
+            east: eastward >= 0.0,
+            street,
+        })
+    }
+}
+
+/// Uniform grid of the road nodes and the segments of the named streets,
+/// to look up addresses without scanning all of them.
+#[derive(Debug, Clone)]
+struct RoadGrid {
+    cell_size: f64,
+    nodes: HashMap<(i64, i64), Vec<usize>>,
+    /// Indices of the streets and of the first nodes of the segments in them.
+    segments: HashMap<(i64, i64), Vec<(usize, usize)>>,
+}
+
+impl RoadGrid {
+    fn new(network: &RoadNetwork, streets: &[NamedStreet], cell_size: f64) -> Self {
+        let mut grid = Self {
+            cell_size: cell_size.max(GRID_CELL_SIZE_MIN),
+            nodes: HashMap::new(),
+            segments: HashMap::new(),
+        };
+        let nodes = network.nodes();
+        for (i, node) in nodes.iter().enumerate() {
+            let cells = grid.cells_around(node.site, node.site).collect::<Vec<_>>();
+            for cell in cells {
+                grid.nodes.entry(cell).or_default().push(i);
+            }
+        }
+        for (street_index, street) in streets.iter().enumerate() {
+            for (i, pair) in street.nodes.windows(2).enumerate() {
+                let cells = grid
+                    .cells_around(nodes[pair[0]].site, nodes[pair[1]].site)
+                    .collect::<Vec<_>>();
+                for cell in cells {
+                    grid.segments
+                        .entry(cell)
+                        .or_default()
+                        .push((street_index, i));
+                }
+            }
+        }
+        grid
+    }
+
+    fn cells_around(&self, start: Site, end: Site) -> impl Iterator<Item = (i64, i64)> {
+        let cell_of = |v: f64| (v / self.cell_size).floor() as i64;
+        let (x0, x1) = (cell_of(start.x.min(end.x)), cell_of(start.x.max(end.x)));
+        let (y0, y1) = (cell_of(start.y.min(end.y)), cell_of(start.y.max(end.y)));
+        (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+    }
+
+    /// Cells within `radius` of `site` in each axis.
+    fn square_around(&self, site: Site, radius: f64) -> impl Iterator<Item = (i64, i64)> {
+        self.cells_around(
+            Site {
+                x: site.x - radius,
+                y: site.y - radius,
+            },
+            Site {
+                x: site.x + radius,
+                y: site.y + radius,
+            },
+        )
+    }
+
+    /// Nodes in the cells within `radius` of `site`, which include all the nodes within `radius`.
+    fn nodes_around(&self, site: Site, radius: f64) -> impl Iterator<Item = usize> + '_ {
+        self.square_around(site, radius)
+            .filter_map(|cell| self.nodes.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    /// Segments in the cells within `radius` of `site`, possibly more than once.
+    fn segments_around(
+        &self,
+        site: Site,
+        radius: f64,
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.square_around(site, radius)
+            .filter_map(|cell| self.segments.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+/// Distance from `site` to the segment between `start` and `end`.
+pub(crate) fn segment_distance(site: Site, start: Site, end: Site) -> f64 {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 > 0.0 {
+        (((site.x - start.x) * dx + (site.y - start.y) * dy) / length2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    site.distance(&Site {
+        x: start.x + dx * t,
+        y: start.y + dy * t,
+    })
+}
@@ -0,0 +1,295 @@
+use std::{fs, ops::RangeInclusive, path::PathBuf, process};
+
+use hokkaido_generator::{
+    buffer::ElevationBuffer, params::GenerationParams, render::RenderStyle,
+    standard_map::StandardMap, types::MapSite,
+};
+use rayon::prelude::*;
+use tiny_skia::{Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+const USAGE: &str = "\
+Usage: hokkaido-cli [OPTIONS]
+
+Options:
+  --seed <SEED>                Generate a single map from SEED
+  --seeds <START>..<END>       Generate maps for every seed in START..END
+  --x-expand-prop <PROP>       Horizontal expansion of the map [default: 1.4]
+  --dataset <PATH>             Placename CSV [default: ../frontend/static/dataset/placenames.csv]
+  --params <PATH>              Generation parameters in JSON (missing fields take the defaults)
+  --output <DIR>               Output directory [default: output]
+  --image-height <PIXELS>      Height of the rendered image [default: 1000]
+  --contour-interval <HEIGHT>  Draw contour lines every HEIGHT, with index contours every 5 lines
+  -h, --help                   Print this message
+";
+
+struct Args {
+    seeds: RangeInclusive<u32>,
+    x_expand_prop: f64,
+    dataset: PathBuf,
+    params: Option<PathBuf>,
+    output: PathBuf,
+    image_height: u32,
+    contour_interval: Option<f64>,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Self {
+            seeds: 0..=0,
+            x_expand_prop: 1.4,
+            dataset: PathBuf::from("../frontend/static/dataset/placenames.csv"),
+            params: None,
+            output: PathBuf::from("output"),
+            image_height: 1000,
+            contour_interval: None,
+        };
+
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            if arg == "-h" || arg == "--help" {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            let value = iter
+                .next()
+                .ok_or_else(|| format!("missing value for `{}`", arg))?;
+            match arg.as_str() {
+                "--seed" => {
+                    let seed = parse_value(&arg, &value)?;
+                    args.seeds = seed..=seed;
+                }
+                "--seeds" => {
+                    let (start, end) = value
+                        .split_once("..")
+                        .ok_or_else(|| format!("invalid range `{}` for `{}`", value, arg))?;
+                    let (start, end): (u32, u32) =
+                        (parse_value(&arg, start)?, parse_value(&arg, end)?);
+                    if end <= start {
+                        return Err(format!("empty range `{}` for `{}`", value, arg));
+                    }
+                    args.seeds = start..=end - 1;
+                }
+                "--x-expand-prop" => args.x_expand_prop = parse_value(&arg, &value)?,
+                "--dataset" => args.dataset = PathBuf::from(value),
+                "--params" => args.params = Some(PathBuf::from(value)),
+                "--output" => args.output = PathBuf::from(value),
+                "--image-height" => args.image_height = parse_value(&arg, &value)?,
+                "--contour-interval" => args.contour_interval = Some(parse_value(&arg, &value)?),
+                _ => return Err(format!("unknown option `{}`", arg)),
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{}` for `{}`", value, arg))
+}
+
+fn main() {
+    let args = Args::parse().unwrap_or_else(|err| {
+        eprintln!("error: {}\n\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    let dataset = fs::read_to_string(&args.dataset).unwrap_or_else(|err| {
+        eprintln!("error: failed to read {}: {}", args.dataset.display(), err);
+        process::exit(1);
+    });
+
+    let params = match &args.params {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|json| GenerationParams::from_json(&json).map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| {
+                eprintln!("error: failed to read {}: {}", path.display(), err);
+                process::exit(1);
+            }),
+        None => GenerationParams::default(),
+    };
+
+    if let Err(err) = fs::create_dir_all(&args.output) {
+        eprintln!("error: failed to create {}: {}", args.output.display(), err);
+        process::exit(1);
+    }
+
+    let failed = args
+        .seeds
+        .clone()
+        .into_par_iter()
+        .filter(|&seed| {
+            if let Err(err) = generate(seed, &args, &dataset, &params) {
+                eprintln!("seed {}: {}", seed, err);
+                true
+            } else {
+                false
+            }
+        })
+        .count();
+
+    if failed > 0 {
+        eprintln!("{} of {} maps failed", failed, args.seeds.clone().count());
+        process::exit(1);
+    }
+}
+
+fn generate(
+    seed: u32,
+    args: &Args,
+    dataset: &str,
+    params: &GenerationParams,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let standard = StandardMap::with_params(seed, args.x_expand_prop, dataset.to_string(), params)?;
+
+    let image_width = (args.image_height as f64 * args.x_expand_prop) as u32;
+    let pixmap = render(
+        &standard,
+        image_width,
+        args.image_height,
+        args.contour_interval,
+    )?;
+    pixmap.save_png(args.output.join(format!("{}.png", seed)))?;
+
+    let metadata = metadata(seed, args.x_expand_prop, &standard);
+    fs::write(
+        args.output.join(format!("{}.json", seed)),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+
+    fs::write(
+        args.output.join(format!("{}.geojson", seed)),
+        standard.to_geojson(None),
+    )?;
+
+    println!(
+        "seed {}: {}{} ({}人)",
+        seed,
+        standard.get_nameset().city_name().name(),
+        standard.get_nameset().government().name(),
+        standard.get_population()
+    );
+    Ok(())
+}
+
+fn metadata(seed: u32, x_expand_prop: f64, standard: &StandardMap) -> serde_json::Value {
+    let nameset = standard.get_nameset();
+    let name = |name: hokkaido_generator::types::Name| {
+        serde_json::json!({
+            "name": name.name(),
+            "reading": name.reading(),
+        })
+    };
+    let origin = standard.get_origin_site();
+    let (bound_min, bound_max) = (standard.bound_min(), standard.bound_max());
+
+    serde_json::json!({
+        "seed": seed,
+        "x_expand_prop": x_expand_prop,
+        "city_name": name(nameset.city_name()),
+        "county_name": name(nameset.county_name()),
+        "subprefecture_name": name(nameset.subprefecture_name()),
+        "subprefecture_postfix": name(nameset.subprefecture_postfix()),
+        "government": name(nameset.government()),
+        "population": standard.get_population(),
+        "origin": [origin.x, origin.y],
+        "initial_angle": standard.get_initial_angle(),
+        "bound_min": [bound_min.x, bound_min.y],
+        "bound_max": [bound_max.x, bound_max.y],
+    })
+}
+
+fn render(
+    standard: &StandardMap,
+    image_width: u32,
+    image_height: u32,
+    contour_interval: Option<f64>,
+) -> Result<Pixmap, Box<dyn std::error::Error>> {
+    let elevation_buffer = ElevationBuffer::from_terrain(standard, image_width, image_height);
+    let mut pixmap = RenderStyle::topographic()
+        .render_pixmap(&elevation_buffer)
+        .ok_or("invalid image size")?;
+
+    let image_x_of = |x: f64| -> f32 {
+        ((x - standard.bound_min().x) / (standard.bound_max().x - standard.bound_min().x)
+            * image_width as f64) as f32
+    };
+    let image_y_of = |y: f64| -> f32 {
+        ((y - standard.bound_min().y) / (standard.bound_max().y - standard.bound_min().y)
+            * image_height as f64) as f32
+    };
+
+    let mut paint = Paint::default();
+
+    let stroke_polyline = |pixmap: &mut Pixmap, paint: &Paint, sites: &[MapSite], width: f32| {
+        let mut builder = PathBuilder::new();
+        for (i, site) in sites.iter().enumerate() {
+            if i == 0 {
+                builder.move_to(image_x_of(site.x), image_y_of(site.y));
+            } else {
+                builder.line_to(image_x_of(site.x), image_y_of(site.y));
+            }
+        }
+        if let Some(dpath) = builder.finish() {
+            let stroke = Stroke {
+                width,
+                ..Default::default()
+            };
+            pixmap.stroke_path(&dpath, paint, &stroke, Transform::identity(), None);
+        }
+    };
+
+    if let Some(interval) = contour_interval {
+        paint.set_color_rgba8(120, 90, 50, 160);
+        for contour in elevation_buffer.contours(interval, interval * 5.0)? {
+            let width = if contour.is_index() { 0.8 } else { 0.3 };
+            stroke_polyline(&mut pixmap, &paint, &contour.sites(), width);
+        }
+    }
+
+    paint.set_color_rgba8(70, 150, 200, 255);
+    for river in standard.river_paths() {
+        let width = 0.5 + 0.4 * river.order() as f32;
+        stroke_polyline(&mut pixmap, &paint, &river.sites(), width);
+    }
+
+    paint.set_color_rgba8(0, 0, 0, 255);
+    for path in standard.network_paths() {
+        let (inode, jnode) = (path.node1().site(), path.node2().site());
+        let width = if path.stage() == 0 { 1.5 } else { 0.5 };
+        let mut builder = PathBuilder::new();
+        builder.move_to(image_x_of(inode.x), image_y_of(inode.y));
+        builder.line_to(image_x_of(jnode.x), image_y_of(jnode.y));
+        if let Some(dpath) = builder.finish() {
+            if path.is_bridge() {
+                let casing = Stroke {
+                    width: width + 2.0,
+                    ..Default::default()
+                };
+                paint.set_color_rgba8(255, 255, 255, 255);
+                pixmap.stroke_path(&dpath, &paint, &casing, Transform::identity(), None);
+                paint.set_color_rgba8(0, 0, 0, 255);
+            }
+            let stroke = Stroke {
+                width,
+                ..Default::default()
+            };
+            pixmap.stroke_path(&dpath, &paint, &stroke, Transform::identity(), None);
+        }
+    }
+
+    paint.set_color_rgba8(255, 0, 0, 255);
+    let origin = standard.get_origin_site();
+    if let Some(rect) = Rect::from_xywh(
+        image_x_of(origin.x) - 2.0,
+        image_y_of(origin.y) - 2.0,
+        4.0,
+        4.0,
+    ) {
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+
+    Ok(pixmap)
+}
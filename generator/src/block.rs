@@ -0,0 +1,93 @@
+use street_engine::core::geometry::site::Site;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    map::Map,
+    types::{to_map_sites, MapSite},
+};
+
+/// A city block enclosed by roads.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Block {
+    sites: Vec<Site>,
+    area: f64,
+    perimeter: f64,
+    population_density: f64,
+}
+
+#[wasm_bindgen]
+impl Block {
+    /// Closed ring of the roads around the block (the first and the last sites are the same).
+    pub fn sites(&self) -> Vec<MapSite> {
+        to_map_sites(&self.sites)
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+
+    /// Length of the roads around the block.
+    pub fn perimeter(&self) -> f64 {
+        self.perimeter
+    }
+
+    /// Population density averaged over the area of the block.
+    pub fn population_density(&self) -> f64 {
+        self.population_density
+    }
+}
+
+impl Block {
+    /// The roads around the block without repeating the first site.
+    pub(crate) fn ring(&self) -> &[Site] {
+        &self.sites[..self.sites.len() - 1]
+    }
+}
+
+/// Extract the blocks enclosed by the road network of the map.
+pub(crate) fn extract(map: &Map) -> Vec<Block> {
+    let nodes = map.network.nodes();
+
+    map.network
+        .faces()
+        .into_iter()
+        .map(|face| {
+            let mut sites = face.iter().map(|&i| nodes[i].site).collect::<Vec<_>>();
+            sites.push(sites[0]);
+            let edges = || sites.windows(2).map(|pair| (pair[0], pair[1]));
+
+            // the density is integrated over the triangles fanning out from the center
+            let center = Site {
+                x: face.iter().map(|&i| nodes[i].site.x).sum::<f64>() / face.len() as f64,
+                y: face.iter().map(|&i| nodes[i].site.y).sum::<f64>() / face.len() as f64,
+            };
+            let (area, weighted_density) =
+                edges().fold((0.0, 0.0), |(area, weighted_density), (a, b)| {
+                    let triangle = ((a.x - center.x) * (b.y - center.y)
+                        - (b.x - center.x) * (a.y - center.y))
+                        / 2.0;
+                    (
+                        area + triangle,
+                        weighted_density
+                            + triangle
+                                * map.population_density_at(
+                                    (center.x + a.x + b.x) / 3.0,
+                                    (center.y + a.y + b.y) / 3.0,
+                                ),
+                    )
+                });
+
+            Block {
+                perimeter: edges().map(|(a, b)| a.distance(&b)).sum(),
+                population_density: if area > 0.0 {
+                    weighted_density / area
+                } else {
+                    0.0
+                },
+                area,
+                sites,
+            }
+        })
+        .collect()
+}
@@ -1,11 +1,19 @@
-use crate::standard_map::StandardMap;
+use crate::{
+    contour,
+    error::RasterError,
+    standard_map::StandardMap,
+    types::{ContourLine, MapSite},
+};
 use rayon::prelude::*;
+use street_engine::core::geometry::site::Site;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 pub struct ElevationBuffer {
     buffer: Vec<f64>,
     image_width: u32,
+    bound_min: Site,
+    bound_max: Site,
 }
 
 #[wasm_bindgen]
@@ -18,33 +26,342 @@ impl ElevationBuffer {
     ) -> ElevationBuffer {
         let (bound_min, bound_max) = (standard.bound_min(), standard.bound_max());
 
-        let buffer = (0..image_height)
-            .into_par_iter()
-            .flat_map(|imagey| {
-                (0..image_width).into_par_iter().map(move |imagex| {
-                    let x = bound_min.x
-                        + (bound_max.x - bound_min.x)
-                            * ((imagex as f64 + 0.5) / image_width as f64);
-                    let y = bound_min.y
-                        + (bound_max.y - bound_min.y)
-                            * ((imagey as f64 + 0.5) / image_height as f64);
-                    let elevation = standard.get_elevation(x, y);
-                    if let Some(elevation) = elevation {
-                        elevation
-                    } else {
-                        0.0
-                    }
-                })
-            })
-            .collect();
+        let mut buffer = vec![0.0; image_width as usize * image_height as usize];
+        sample_into(standard, image_width, image_height, &mut buffer, |x, y| {
+            standard.get_elevation(x, y).unwrap_or(0.0)
+        });
 
         ElevationBuffer {
             buffer,
             image_width,
+            bound_min: Site {
+                x: bound_min.x,
+                y: bound_min.y,
+            },
+            bound_max: Site {
+                x: bound_max.x,
+                y: bound_max.y,
+            },
         }
     }
 
+    /// Sample the elevations of the map into `out` (row-major, `image_width * image_height` values),
+    /// reusing a buffer of the caller.
+    ///
+    /// From JS, `out` is copied into the wasm memory and back, so this saves the allocation
+    /// of the caller's array but not the copy.
+    /// Fails if the length of `out` does not match the image size.
+    pub fn compute_into(
+        standard: &StandardMap,
+        image_width: u32,
+        image_height: u32,
+        out: &mut [f64],
+    ) -> Result<(), RasterError> {
+        check_len(out, image_width, image_height)?;
+        sample_into(standard, image_width, image_height, out, |x, y| {
+            standard.get_elevation(x, y).unwrap_or(0.0)
+        });
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.image_width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.dimensions().1 as u32
+    }
+
     pub fn get_elevation(&self, x: u32, y: u32) -> f64 {
         self.buffer[(y * self.image_width + x) as usize]
     }
+
+    /// A `Float64Array` viewing the elevations directly in the wasm memory (row-major).
+    ///
+    /// The view is invalidated when the buffer is freed or the wasm memory grows,
+    /// so copy it (e.g. with `slice()`) before calling into the generator again.
+    pub fn elevations_view(&self) -> js_sys::Float64Array {
+        // SAFETY: the view does not outlive the buffer as long as the caller follows the above rule.
+        unsafe { js_sys::Float64Array::view(&self.buffer) }
+    }
+
+    /// A copy of the elevations in single precision (row-major), returned as a `Float32Array`.
+    pub fn elevations_f32(&self) -> Vec<f32> {
+        self.buffer.par_iter().map(|&value| value as f32).collect()
+    }
+
+    /// Contour lines at every multiple of `interval` above zero.
+    ///
+    /// Lines at multiples of `index_interval` are flagged as index contours.
+    /// Fails if the interval is not positive or too small for the elevations.
+    pub fn contours(
+        &self,
+        interval: f64,
+        index_interval: f64,
+    ) -> Result<Vec<ContourLine>, RasterError> {
+        let max = self.buffer.iter().copied().fold(0.0, f64::max);
+        Ok(contour::extract(
+            self,
+            &contour::levels_by_interval(interval, max)?,
+            index_interval,
+        ))
+    }
+
+    /// Contour lines at the given levels.
+    ///
+    /// Lines at multiples of `index_interval` are flagged as index contours.
+    pub fn contours_at(&self, levels: Vec<f64>, index_interval: f64) -> Vec<ContourLine> {
+        contour::extract(self, &levels, index_interval)
+    }
+
+    /// Slope of each pixel in radians.
+    pub fn slope(&self) -> Vec<f64> {
+        self.gradients()
+            .into_par_iter()
+            .map(|(dzdx, dzdy)| dzdx.hypot(dzdy).atan())
+            .collect()
+    }
+
+    /// Aspect (the direction the slope faces) of each pixel in radians,
+    /// measured clockwise from the north (the negative y direction of the map).
+    ///
+    /// Flat pixels have `NaN`.
+    pub fn aspect(&self) -> Vec<f64> {
+        self.gradients()
+            .into_par_iter()
+            .map(|(dzdx, dzdy)| {
+                if dzdx == 0.0 && dzdy == 0.0 {
+                    return f64::NAN;
+                }
+                // downslope direction in (east, north)
+                (-dzdx).atan2(dzdy).rem_euclid(std::f64::consts::PI * 2.0)
+            })
+            .collect()
+    }
+
+    /// Hillshade of each pixel in `[0, 1]`.
+    ///
+    /// The sun is at `azimuth_deg` (clockwise from the north) and `altitude_deg` above the horizon.
+    /// Elevations are multiplied by `z_factor` to exaggerate the relief.
+    pub fn hillshade(&self, azimuth_deg: f64, altitude_deg: f64, z_factor: f64) -> Vec<f64> {
+        let (azimuth, altitude) = (azimuth_deg.to_radians(), altitude_deg.to_radians());
+        let light = [
+            azimuth.sin() * altitude.cos(),
+            azimuth.cos() * altitude.cos(),
+            altitude.sin(),
+        ];
+        self.gradients()
+            .into_par_iter()
+            .map(|(dzdx, dzdy)| {
+                // surface normal in (east, north, up)
+                let normal = [-dzdx * z_factor, dzdy * z_factor, 1.0];
+                let norm = normal.iter().map(|v| v * v).sum::<f64>().sqrt();
+                let shade = normal.iter().zip(light).map(|(n, l)| n * l).sum::<f64>() / norm;
+                shade.max(0.0)
+            })
+            .collect()
+    }
+}
+
+impl ElevationBuffer {
+    /// The elevations in row-major order.
+    pub fn as_slice(&self) -> &[f64] {
+        &self.buffer
+    }
+
+    pub(crate) fn dimensions(&self) -> (usize, usize) {
+        let width = self.image_width as usize;
+        (width, self.buffer.len().checked_div(width).unwrap_or(0))
+    }
+
+    /// Gradients `(dz/dx, dz/dy)` of each pixel by Horn's method.
+    fn gradients(&self) -> Vec<(f64, f64)> {
+        let (width, height) = self.dimensions();
+        let pixel_width = (self.bound_max.x - self.bound_min.x) / width as f64;
+        let pixel_height = (self.bound_max.y - self.bound_min.y) / height as f64;
+
+        let value = |x: isize, y: isize| {
+            let x = x.clamp(0, width as isize - 1) as usize;
+            let y = y.clamp(0, height as isize - 1) as usize;
+            self.buffer[y * width + x]
+        };
+
+        (0..width * height)
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = ((i % width) as isize, (i / width) as isize);
+                let dzdx = ((value(x + 1, y - 1) + 2.0 * value(x + 1, y) + value(x + 1, y + 1))
+                    - (value(x - 1, y - 1) + 2.0 * value(x - 1, y) + value(x - 1, y + 1)))
+                    / (8.0 * pixel_width);
+                let dzdy = ((value(x - 1, y + 1) + 2.0 * value(x, y + 1) + value(x + 1, y + 1))
+                    - (value(x - 1, y - 1) + 2.0 * value(x, y - 1) + value(x + 1, y - 1)))
+                    / (8.0 * pixel_height);
+                (dzdx, dzdy)
+            })
+            .collect()
+    }
+
+    pub(crate) fn bounds(&self) -> (MapSite, MapSite) {
+        (
+            MapSite {
+                x: self.bound_min.x,
+                y: self.bound_min.y,
+            },
+            MapSite {
+                x: self.bound_max.x,
+                y: self.bound_max.y,
+            },
+        )
+    }
+}
+
+/// Population density of each pixel of the map, e.g. to render heatmaps.
+#[wasm_bindgen]
+pub struct DensityBuffer {
+    buffer: Vec<f64>,
+    image_width: u32,
+}
+
+#[wasm_bindgen]
+impl DensityBuffer {
+    #[wasm_bindgen(constructor)]
+    pub fn from_map(standard: &StandardMap, image_width: u32, image_height: u32) -> DensityBuffer {
+        let mut buffer = vec![0.0; image_width as usize * image_height as usize];
+        sample_into(standard, image_width, image_height, &mut buffer, |x, y| {
+            standard.get_population_density(x, y)
+        });
+
+        DensityBuffer {
+            buffer,
+            image_width,
+        }
+    }
+
+    /// Sample the population densities of the map into `out` (row-major, `image_width * image_height` values),
+    /// reusing a buffer of the caller.
+    ///
+    /// From JS, `out` is copied into the wasm memory and back, so this saves the allocation
+    /// of the caller's array but not the copy.
+    /// Fails if the length of `out` does not match the image size.
+    pub fn compute_into(
+        standard: &StandardMap,
+        image_width: u32,
+        image_height: u32,
+        out: &mut [f64],
+    ) -> Result<(), RasterError> {
+        check_len(out, image_width, image_height)?;
+        sample_into(standard, image_width, image_height, out, |x, y| {
+            standard.get_population_density(x, y)
+        });
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.image_width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.buffer
+            .len()
+            .checked_div(self.image_width as usize)
+            .unwrap_or(0) as u32
+    }
+
+    pub fn get_population_density(&self, x: u32, y: u32) -> f64 {
+        self.buffer[(y * self.image_width + x) as usize]
+    }
+
+    /// A copy of the densities in single precision (row-major), returned as a `Float32Array`.
+    pub fn densities_f32(&self) -> Vec<f32> {
+        self.buffer.par_iter().map(|&value| value as f32).collect()
+    }
+}
+
+impl DensityBuffer {
+    /// The densities in row-major order.
+    pub fn as_slice(&self) -> &[f64] {
+        &self.buffer
+    }
+}
+
+/// Fails if the length of `out` is not `image_width * image_height`.
+fn check_len(out: &[f64], image_width: u32, image_height: u32) -> Result<(), RasterError> {
+    let expected = image_width as usize * image_height as usize;
+    if out.len() != expected {
+        return Err(RasterError::BufferLength(out.len(), expected));
+    }
+    Ok(())
+}
+
+/// Sample `sample(x, y)` at the center of each pixel of the map into `out`,
+/// which is `image_width * image_height` long.
+fn sample_into(
+    standard: &StandardMap,
+    image_width: u32,
+    image_height: u32,
+    out: &mut [f64],
+    sample: impl Fn(f64, f64) -> f64 + Sync,
+) {
+    if image_width == 0 {
+        return;
+    }
+    let (bound_min, bound_max) = (standard.bound_min(), standard.bound_max());
+
+    out.par_chunks_mut(image_width as usize)
+        .enumerate()
+        .for_each(|(imagey, row)| {
+            row.iter_mut().enumerate().for_each(|(imagex, value)| {
+                let x = bound_min.x
+                    + (bound_max.x - bound_min.x) * ((imagex as f64 + 0.5) / image_width as f64);
+                let y = bound_min.y
+                    + (bound_max.y - bound_min.y) * ((imagey as f64 + 0.5) / image_height as f64);
+                *value = sample(x, y);
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relief_of_plane() {
+        // a plane rising to the east by 1 per unit
+        let (width, height) = (4, 3);
+        let buffer = ElevationBuffer {
+            buffer: (0..width * height).map(|i| (i % width) as f64).collect(),
+            image_width: width as u32,
+            bound_min: Site { x: 0.0, y: 0.0 },
+            bound_max: Site {
+                x: width as f64,
+                y: height as f64,
+            },
+        };
+
+        let slope = buffer.slope();
+        assert!((slope[5] - std::f64::consts::FRAC_PI_4).abs() < 1e-9);
+
+        // facing west
+        let aspect = buffer.aspect();
+        assert!((aspect[5] - std::f64::consts::PI * 1.5).abs() < 1e-9);
+
+        let from_west = buffer.hillshade(270.0, 45.0, 1.0);
+        let from_east = buffer.hillshade(90.0, 45.0, 1.0);
+        assert!((from_west[5] - 1.0).abs() < 1e-9);
+        assert_eq!(from_east[5], 0.0);
+    }
+
+    #[test]
+    fn test_check_len() {
+        assert!(check_len(&[0.0; 6], 3, 2).is_ok());
+        assert!(matches!(
+            check_len(&[0.0; 5], 3, 2),
+            Err(RasterError::BufferLength(5, 6))
+        ));
+        assert!(check_len(&[], 0, 2).is_ok());
+    }
 }
@@ -0,0 +1,212 @@
+use rand::{rngs::StdRng, Rng};
+use street_engine::core::geometry::site::Site;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    address::segment_distance,
+    block::Block,
+    isoline::ring_contains,
+    map::{random::RandomStream, Map},
+    params::GenerationParams,
+    types::{to_map_sites, MapSite},
+};
+
+/// A rectangular building facing a road.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Building {
+    corners: [Site; 4],
+    floors: u32,
+    height: f64,
+}
+
+#[wasm_bindgen]
+impl Building {
+    /// Closed ring of the footprint (the first and the last sites are the same).
+    pub fn sites(&self) -> Vec<MapSite> {
+        let mut ring = self.corners.to_vec();
+        ring.push(self.corners[0]);
+        to_map_sites(&ring)
+    }
+
+    pub fn floors(&self) -> u32 {
+        self.floors
+    }
+
+    /// Height in the units of the map.
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+}
+
+impl Building {
+    pub(crate) fn new(corners: [Site; 4], floors: u32, height: f64) -> Self {
+        Self {
+            corners,
+            floors,
+            height,
+        }
+    }
+
+    pub(crate) fn corners(&self) -> &[Site; 4] {
+        &self.corners
+    }
+}
+
+/// Place buildings along the roads around the blocks of the map.
+///
+/// Lots are built where the population density is high enough, and denser lots have taller buildings.
+pub(crate) fn generate(
+    map: &Map,
+    blocks: &[Block],
+    params: &GenerationParams,
+    seed: u32,
+) -> Vec<Building> {
+    let mut rnd = RandomStream::Buildings.rng(seed);
+    blocks
+        .iter()
+        .flat_map(|block| {
+            place(
+                block.ring(),
+                |site| map.population_density_at(site.x, site.y),
+                params,
+                &mut rnd,
+            )
+        })
+        .collect()
+}
+
+/// Place buildings in the lots along each side of the ring, which is counterclockwise with the y axis pointing up.
+fn place(
+    ring: &[Site],
+    population_density: impl Fn(Site) -> f64,
+    params: &GenerationParams,
+    rnd: &mut StdRng,
+) -> Vec<Building> {
+    let polygon = ring.iter().map(|site| (site.x, site.y)).collect::<Vec<_>>();
+    let lot_width = params.building_lot_width;
+
+    let mut buildings: Vec<Building> = Vec::new();
+    for (start, end) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+        let length = start.distance(end);
+        let lots = (length / lot_width) as usize;
+        if lots == 0 {
+            continue;
+        }
+        let along = ((end.x - start.x) / length, (end.y - start.y) / length);
+        // the block is on the left of the side
+        let inward = (-along.1, along.0);
+        let margin = (length - lots as f64 * lot_width) / 2.0;
+
+        for lot in 0..lots {
+            let width = lot_width * rnd.gen_range(0.6..0.9);
+            let depth = lot_width * rnd.gen_range(0.6..1.4);
+            let scale = rnd.gen_range(0.5..1.5);
+
+            let offset = margin + (lot as f64 + 0.5) * lot_width - width / 2.0;
+            let corner = |a: f64, b: f64| Site {
+                x: start.x + along.0 * a + inward.0 * b,
+                y: start.y + along.1 * a + inward.1 * b,
+            };
+            let front = params.building_setback;
+            let corners = [
+                corner(offset, front),
+                corner(offset + width, front),
+                corner(offset + width, front + depth),
+                corner(offset, front + depth),
+            ];
+            let density = population_density(corner(offset + width / 2.0, front + depth / 2.0));
+            if density < params.building_density_min
+                || !is_inside(&corners, ring, &polygon, front)
+                || buildings
+                    .iter()
+                    .any(|building| overlaps(building.corners(), &corners))
+            {
+                continue;
+            }
+
+            let floors =
+                (1.0 + params.building_floors_max.saturating_sub(1) as f64 * density * scale)
+                    .round()
+                    .clamp(1.0, params.building_floors_max.max(1) as f64) as u32;
+            buildings.push(Building::new(
+                corners,
+                floors,
+                floors as f64 * params.building_floor_height,
+            ));
+        }
+    }
+    buildings
+}
+
+/// Whether the footprint is inside the block and at least `setback` away from all of its sides.
+fn is_inside(corners: &[Site; 4], ring: &[Site], polygon: &[(f64, f64)], setback: f64) -> bool {
+    let footprint = corners.map(|site| (site.x, site.y));
+    corners.iter().all(|&site| {
+        ring_contains(polygon, (site.x, site.y))
+            && ring
+                .iter()
+                .zip(ring.iter().cycle().skip(1))
+                .all(|(&start, &end)| segment_distance(site, start, end) >= setback - 1e-9)
+    }) && !ring
+        .iter()
+        .any(|site| ring_contains(&footprint, (site.x, site.y)))
+}
+
+/// Whether two convex quadrilaterals overlap, by the separating axis theorem.
+fn overlaps(a: &[Site; 4], b: &[Site; 4]) -> bool {
+    let axes = a
+        .iter()
+        .zip(a.iter().cycle().skip(1))
+        .chain(b.iter().zip(b.iter().cycle().skip(1)))
+        .map(|(p, q)| (q.y - p.y, p.x - q.x));
+    let project = |corners: &[Site; 4], axis: (f64, f64)| {
+        corners
+            .iter()
+            .map(|site| site.x * axis.0 + site.y * axis.1)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+                (min.min(value), max.max(value))
+            })
+    };
+    axes.into_iter().all(|axis| {
+        let ((a_min, a_max), (b_min, b_max)) = (project(a, axis), project(b, axis));
+        a_max > b_min && b_max > a_min
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_place() {
+        let ring = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)].map(|(x, y)| Site::new(x, y));
+        let params = GenerationParams::default();
+        let mut rnd = StdRng::seed_from_u64(0);
+
+        let buildings = place(&ring, |_| 0.5, &params, &mut rnd);
+        assert!(buildings.len() > 10);
+        for (i, building) in buildings.iter().enumerate() {
+            assert!(building
+                .corners()
+                .iter()
+                .all(|site| site.x >= params.building_setback
+                    && site.x <= 2.0 - params.building_setback
+                    && site.y >= params.building_setback
+                    && site.y <= 2.0 - params.building_setback));
+            assert!((1..=params.building_floors_max).contains(&building.floors()));
+            assert!(buildings[..i]
+                .iter()
+                .all(|other| !overlaps(other.corners(), building.corners())));
+        }
+
+        let low = place(&ring, |_| params.building_density_min, &params, &mut rnd);
+        assert!(!low.is_empty());
+        assert!(low.iter().all(|building| building.floors() == 1));
+        assert!(place(&ring, |site| site.x - 1.0, &params, &mut rnd)
+            .iter()
+            .all(|building| building.corners().iter().all(|site| site.x > 1.0)));
+    }
+}
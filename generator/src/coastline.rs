@@ -0,0 +1,82 @@
+use street_engine::core::geometry::site::Site;
+
+use crate::{
+    buffer::ElevationBuffer,
+    isoline::{self, Isoline},
+    types::LandPolygon,
+};
+
+/// Extract land polygons from the elevation buffer.
+///
+/// The buffer is padded with sea, so every coastline is closed even if the land reaches the
+/// edge of the map. Lakes (inland areas below `sea_level`) become holes of the polygons.
+pub fn extract(buffer: &ElevationBuffer, sea_level: f64) -> Vec<LandPolygon> {
+    let (width, height) = buffer.dimensions();
+    let (bound_min, bound_max) = buffer.bounds();
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    let (padded_width, padded_height) = (width + 2, height + 2);
+    let padded = (0..padded_width * padded_height)
+        .map(|i| {
+            let (x, y) = (i % padded_width, i / padded_width);
+            if x == 0 || y == 0 || x == padded_width - 1 || y == padded_height - 1 {
+                f64::NEG_INFINITY
+            } else {
+                buffer.as_slice()[(y - 1) * width + (x - 1)]
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // the padding lies on the bounds, and the inner grid points on the centers of the pixels
+    let axis_of = |g: f64, size: usize, min: f64, max: f64| -> f64 {
+        let center = |i: f64| min + (max - min) * ((i + 0.5) / size as f64);
+        if g <= 1.0 {
+            min + (center(0.0) - min) * g
+        } else if g >= size as f64 {
+            center(size as f64 - 1.0) + (max - center(size as f64 - 1.0)) * (g - size as f64)
+        } else {
+            center(g - 1.0)
+        }
+    };
+    let site_of = |(gx, gy): (f64, f64)| Site {
+        x: axis_of(gx, width, bound_min.x, bound_max.x),
+        y: axis_of(gy, height, bound_min.y, bound_max.y),
+    };
+
+    let (exteriors, holes): (Vec<Isoline>, Vec<Isoline>) =
+        isoline::trace(&padded, padded_width, padded_height, sea_level)
+            .into_iter()
+            .filter(|line| line.closed)
+            .partition(|line| isoline::signed_area(&line.points) < 0.0);
+
+    let mut polygons = exteriors
+        .iter()
+        .map(|exterior| (exterior, Vec::new()))
+        .collect::<Vec<_>>();
+
+    holes.iter().for_each(|hole| {
+        // the innermost land polygon containing the lake
+        let container = polygons
+            .iter_mut()
+            .filter(|(exterior, _)| isoline::ring_contains(&exterior.points, hole.points[0]))
+            .min_by(|(a, _), (b, _)| {
+                isoline::signed_area(&b.points).total_cmp(&isoline::signed_area(&a.points))
+            });
+        if let Some((_, container_holes)) = container {
+            container_holes.push(hole);
+        }
+    });
+
+    polygons
+        .into_iter()
+        .map(|(exterior, holes)| {
+            let to_sites = |line: &Isoline| line.points.iter().map(|&p| site_of(p)).collect();
+            LandPolygon::new(
+                to_sites(exterior),
+                holes.into_iter().map(to_sites).collect(),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,74 @@
+use rayon::prelude::*;
+use street_engine::core::geometry::site::Site;
+
+use crate::{buffer::ElevationBuffer, error::RasterError, isoline, types::ContourLine};
+
+/// Upper limit of the number of levels, which keeps a tiny interval from tracing the buffer forever.
+const LEVELS_MAX: usize = 10_000;
+
+/// Levels at every multiple of `interval` in `(0, max]`.
+///
+/// Fails if the interval is not positive or yields more than [`LEVELS_MAX`] levels.
+pub fn levels_by_interval(interval: f64, max: f64) -> Result<Vec<f64>, RasterError> {
+    if interval.is_nan() || interval <= 0.0 || max / interval > LEVELS_MAX as f64 {
+        return Err(RasterError::ContourInterval(interval));
+    }
+    let count = (max / interval).floor().max(0.0) as usize;
+    Ok((1..=count)
+        .map(|i| i as f64 * interval)
+        .take_while(|level| *level <= max)
+        .collect())
+}
+
+/// Trace contour lines of the elevation buffer at each level.
+///
+/// A line is an index contour if its level is a multiple of `index_interval`.
+pub fn extract(buffer: &ElevationBuffer, levels: &[f64], index_interval: f64) -> Vec<ContourLine> {
+    let (width, height) = buffer.dimensions();
+    let (bound_min, bound_max) = buffer.bounds();
+
+    let site_of = |(gx, gy): (f64, f64)| Site {
+        x: bound_min.x + (bound_max.x - bound_min.x) * ((gx + 0.5) / width as f64),
+        y: bound_min.y + (bound_max.y - bound_min.y) * ((gy + 0.5) / height as f64),
+    };
+
+    let is_index = |level: f64| {
+        index_interval > 0.0 && {
+            let ratio = level / index_interval;
+            (ratio - ratio.round()).abs() < 1e-9
+        }
+    };
+
+    levels
+        .par_iter()
+        .flat_map_iter(|&level| {
+            isoline::trace(buffer.as_slice(), width, height, level)
+                .into_iter()
+                .map(move |line| {
+                    ContourLine::new(
+                        level,
+                        is_index(level),
+                        line.closed,
+                        line.points.into_iter().map(site_of).collect(),
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_by_interval() {
+        assert_eq!(
+            levels_by_interval(10.0, 35.0).unwrap(),
+            vec![10.0, 20.0, 30.0]
+        );
+        assert_eq!(levels_by_interval(10.0, 5.0).unwrap(), Vec::<f64>::new());
+        assert!(levels_by_interval(0.0, 5.0).is_err());
+        assert!(levels_by_interval(f64::NAN, 5.0).is_err());
+        assert!(levels_by_interval(1e-9, 5.0).is_err());
+    }
+}
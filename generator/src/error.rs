@@ -0,0 +1,89 @@
+use fastlem::{
+    lem::generator::GenerationError as TerrainGenerationError,
+    models::surface::builder::ModelBuilderError,
+};
+use thiserror::Error;
+use wasm_bindgen::prelude::*;
+
+/// Errors that can occur while generating a map.
+#[derive(Error, Debug)]
+pub enum GenerationError {
+    #[error("Failed to build terrain model: {0}")]
+    TerrainModel(#[from] ModelBuilderError),
+    #[error("Failed to generate terrain: {0}")]
+    Terrain(#[from] TerrainGenerationError),
+    #[error("No outlet found")]
+    NoOutlet,
+    #[error("Failed to find origin site")]
+    OriginNotFound,
+    #[error("The given origin is not on the land")]
+    InvalidOrigin,
+    #[error("Failed to add origin")]
+    OriginInsertion,
+    #[error("Failed to generate name")]
+    NameGeneration,
+    #[error("Invalid generation parameters: {0}")]
+    Params(String),
+    #[error("Invalid snapshot: {0}")]
+    Snapshot(String),
+    #[error("Generation was cancelled")]
+    Cancelled,
+}
+
+impl GenerationError {
+    /// Machine-readable identifier of the error, exposed to JS as `kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GenerationError::TerrainModel(_) => "terrain_model",
+            GenerationError::Terrain(_) => "terrain",
+            GenerationError::NoOutlet => "no_outlet",
+            GenerationError::OriginNotFound => "origin_not_found",
+            GenerationError::InvalidOrigin => "invalid_origin",
+            GenerationError::OriginInsertion => "origin_insertion",
+            GenerationError::NameGeneration => "name_generation",
+            GenerationError::Params(_) => "params",
+            GenerationError::Snapshot(_) => "snapshot",
+            GenerationError::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Errors that can occur while computing rasters of a generated map.
+#[derive(Error, Debug)]
+pub enum RasterError {
+    #[error("Buffer length {0} does not match the image size {1}")]
+    BufferLength(usize, usize),
+    #[error("Invalid contour interval: {0}")]
+    ContourInterval(f64),
+}
+
+impl RasterError {
+    /// Machine-readable identifier of the error, exposed to JS as `kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RasterError::BufferLength(..) => "buffer_length",
+            RasterError::ContourInterval(_) => "contour_interval",
+        }
+    }
+}
+
+/// A JS `Error` named `name`, with the machine-readable `kind`.
+fn to_js_error(name: &str, message: &str, kind: &str) -> JsValue {
+    let js_error = js_sys::Error::new(message);
+    js_error.set_name(name);
+    // `Reflect::set` only fails on frozen or non-object targets.
+    let _ = js_sys::Reflect::set(&js_error, &"kind".into(), &kind.into());
+    js_error.into()
+}
+
+impl From<GenerationError> for JsValue {
+    fn from(error: GenerationError) -> Self {
+        to_js_error("GenerationError", &error.to_string(), error.kind())
+    }
+}
+
+impl From<RasterError> for JsValue {
+    fn from(error: RasterError) -> Self {
+        to_js_error("RasterError", &error.to_string(), error.kind())
+    }
+}
@@ -0,0 +1,260 @@
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    poi::PoiKind,
+    standard_map::StandardMap,
+    types::{MapSite, Name},
+};
+
+/// Projection from map coordinates to longitude / latitude (WGS 84).
+///
+/// The map origin `(0, 0)` is placed at (`origin_lon`, `origin_lat`) and
+/// one unit of the map is `meters_per_unit` meters. The y axis of the map points south.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct GeoProjection {
+    pub origin_lon: f64,
+    pub origin_lat: f64,
+    pub meters_per_unit: f64,
+}
+
+#[wasm_bindgen]
+impl GeoProjection {
+    #[wasm_bindgen(constructor)]
+    pub fn new(origin_lon: f64, origin_lat: f64, meters_per_unit: f64) -> Self {
+        Self {
+            origin_lon,
+            origin_lat,
+            meters_per_unit,
+        }
+    }
+}
+
+impl Default for GeoProjection {
+    fn default() -> Self {
+        // around the center of Hokkaido
+        Self::new(142.8, 43.4, 100.0)
+    }
+}
+
+impl GeoProjection {
+    fn project(&self, x: f64, y: f64) -> [f64; 2] {
+        let meters_per_degree = 111_320.0;
+        let lat = self.origin_lat - y * self.meters_per_unit / meters_per_degree;
+        let lon = self.origin_lon
+            + x * self.meters_per_unit / (meters_per_degree * self.origin_lat.to_radians().cos());
+        [lon, lat]
+    }
+
+    fn project_site(&self, site: &MapSite) -> [f64; 2] {
+        self.project(site.x, site.y)
+    }
+}
+
+/// Build a GeoJSON FeatureCollection of the whole map.
+pub fn feature_collection(standard: &StandardMap, projection: &GeoProjection) -> Value {
+    let nameset = standard.get_nameset();
+    let name_properties = |key: &str, name: Name| {
+        [
+            (key.to_string(), json!(name.name())),
+            (format!("{}_reading", key), json!(name.reading())),
+        ]
+    };
+    let origin_properties = [
+        name_properties("city_name", nameset.city_name()),
+        name_properties("county_name", nameset.county_name()),
+        name_properties("subprefecture_name", nameset.subprefecture_name()),
+        name_properties("subprefecture_postfix", nameset.subprefecture_postfix()),
+        name_properties("government", nameset.government()),
+    ]
+    .into_iter()
+    .flatten()
+    .chain([
+        ("kind".to_string(), json!("origin")),
+        ("population".to_string(), json!(standard.get_population())),
+    ])
+    .collect::<serde_json::Map<_, _>>();
+
+    let origin = json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "Point",
+            "coordinates": projection.project_site(&standard.get_origin_site()),
+        },
+        "properties": origin_properties,
+    });
+
+    // the main town is the origin
+    let satellites = standard
+        .settlements()
+        .into_iter()
+        .skip(1)
+        .map(|settlement| {
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": projection.project_site(&settlement.site()),
+                },
+                "properties": {
+                    "kind": "settlement",
+                    "name": settlement.name().name(),
+                    "name_reading": settlement.name().reading(),
+                    "population": settlement.population(),
+                },
+            })
+        });
+
+    let railway = json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "LineString",
+            "coordinates": standard
+                .railway_sites()
+                .iter()
+                .map(|site| projection.project_site(site))
+                .collect::<Vec<_>>(),
+        },
+        "properties": {
+            "kind": "railway",
+        },
+    });
+    let stations = standard.stations().into_iter().map(|station| {
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": projection.project_site(&station.site()),
+            },
+            "properties": {
+                "kind": "station",
+                "name": format!("{}駅", station.name().name()),
+                "name_reading": format!("{} eki", station.name().reading()),
+            },
+        })
+    });
+
+    let pois = standard.pois().into_iter().map(|poi| {
+        let category = match poi.kind() {
+            PoiKind::TownHall => "town_hall",
+            PoiKind::School => "school",
+            PoiKind::PostOffice => "post_office",
+            PoiKind::Shrine => "shrine",
+            PoiKind::FishingPort => "fishing_port",
+            PoiKind::RoadStation => "road_station",
+        };
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": projection.project_site(&poi.site()),
+            },
+            "properties": {
+                "kind": "poi",
+                "category": category,
+                "name": poi.name().name(),
+                "name_reading": poi.name().reading(),
+            },
+        })
+    });
+
+    let roads = standard.network_paths().into_iter().map(|path| {
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": [
+                    projection.project_site(&path.node1().site()),
+                    projection.project_site(&path.node2().site()),
+                ],
+            },
+            "properties": {
+                "kind": "road",
+                "stage": path.stage(),
+                "bridge": path.is_bridge(),
+            },
+        })
+    });
+
+    // heights are in meters, to be extruded as they are
+    let buildings = standard.buildings().into_iter().map(|building| {
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [building
+                    .sites()
+                    .iter()
+                    .map(|site| projection.project_site(site))
+                    .collect::<Vec<_>>()],
+            },
+            "properties": {
+                "kind": "building",
+                "floors": building.floors(),
+                "height": building.height() * projection.meters_per_unit,
+            },
+        })
+    });
+
+    let (bound_min, bound_max) = (standard.bound_min(), standard.bound_max());
+    let lands = standard
+        .coastline(
+            (bound_max.x - bound_min.x).ceil() as u32,
+            (bound_max.y - bound_min.y).ceil() as u32,
+        )
+        .into_iter()
+        .map(|land| {
+            let project_ring = |ring: Vec<MapSite>| {
+                ring.iter()
+                    .map(|site| projection.project_site(site))
+                    .collect::<Vec<_>>()
+            };
+            let rings = std::iter::once(project_ring(land.exterior()))
+                .chain((0..land.holes_len()).map(|i| project_ring(land.hole(i))))
+                .collect::<Vec<_>>();
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": rings,
+                },
+                "properties": {
+                    "kind": "land",
+                    "area": land.area(),
+                },
+            })
+        });
+
+    let rivers = standard.river_paths().into_iter().map(|river| {
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": river
+                    .sites()
+                    .iter()
+                    .map(|site| projection.project_site(site))
+                    .collect::<Vec<_>>(),
+            },
+            "properties": {
+                "kind": "river",
+                "order": river.order(),
+            },
+        })
+    });
+
+    json!({
+        "type": "FeatureCollection",
+        "features": lands
+            .chain(rivers)
+            .chain(roads)
+            .chain(buildings)
+            .chain((standard.railway_sites().len() > 1).then_some(railway))
+            .chain(stations)
+            .chain(pois)
+            .chain(std::iter::once(origin))
+            .chain(satellites)
+            .collect::<Vec<_>>(),
+    })
+}
@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    error::GenerationError,
+    map::{generator::MapGenerator, road::RoadNetwork},
+    progress::Progress,
+    standard_map::RulesFn,
+    types::{NetworkNode, NetworkPath},
+};
+
+/// The road network growing from the origins of the settlements, e.g. for animation.
+///
+/// The network after `k` iterations is the one in the middle of the generation of the map,
+/// and the network after the last iteration is the one of the map before the highways
+/// between the settlements are laid.
+///
+/// `TransportBuilder` does not expose its network while iterating, so each step grows the
+/// network again from the origins, which takes time in proportion to the iterations so far.
+#[wasm_bindgen]
+pub struct RoadGrowth {
+    generator: MapGenerator<RulesFn>,
+    network: RoadNetwork,
+    iterations: usize,
+    finished: bool,
+}
+
+impl RoadGrowth {
+    pub(crate) fn new(generator: MapGenerator<RulesFn>) -> Self {
+        Self {
+            generator,
+            network: RoadNetwork::default(),
+            iterations: 0,
+            finished: false,
+        }
+    }
+
+    fn to_network_path(network: &RoadNetwork, index: usize) -> NetworkPath {
+        let (nodes, path) = (network.nodes(), &network.paths()[index]);
+        NetworkPath::new(
+            NetworkNode::new(path.start, nodes[path.start]),
+            NetworkNode::new(path.end, nodes[path.end]),
+            path.is_bridge,
+        )
+    }
+}
+
+#[wasm_bindgen]
+impl RoadGrowth {
+    /// Run `n` more iterations and return the paths added by them.
+    ///
+    /// A path split at a new intersection is replaced by its parts, which are returned as added.
+    pub fn step(&mut self, n: usize) -> Result<Vec<NetworkPath>, GenerationError> {
+        if n == 0 || self.finished {
+            return Ok(Vec::new());
+        }
+        let grown = self
+            .generator
+            .grow_roads(self.iterations.saturating_add(n), &Progress::default())?;
+        let network =
+            RoadNetwork::from_path_network(&grown.network, self.generator.river_network());
+
+        let previous = self
+            .network
+            .paths()
+            .iter()
+            .map(|path| (path.start, path.end))
+            .collect::<HashSet<_>>();
+        let added = (0..network.paths().len())
+            .filter(|&i| {
+                let path = &network.paths()[i];
+                !previous.contains(&(path.start, path.end))
+            })
+            .map(|i| Self::to_network_path(&network, i))
+            .collect();
+
+        self.network = network;
+        self.iterations = grown.iterations;
+        self.finished = grown.finished;
+        Ok(added)
+    }
+
+    /// Paths of the network grown so far.
+    pub fn paths(&self) -> Vec<NetworkPath> {
+        (0..self.network.paths().len())
+            .map(|i| Self::to_network_path(&self.network, i))
+            .collect()
+    }
+
+    /// Number of iterations which added roads so far.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Whether the network has grown as much as the generated map.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Go back to the origins.
+    pub fn reset(&mut self) {
+        self.network = RoadNetwork::default();
+        self.iterations = 0;
+        self.finished = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        params::GenerationParams,
+        standard_map::{create_road_growth, StandardMap},
+    };
+
+    #[test]
+    fn test_road_growth() {
+        let params = GenerationParams {
+            bound: 100.0,
+            particle_num: 2000,
+            ..Default::default()
+        };
+        let mut growth = create_road_growth(1, 1.4, &params).unwrap();
+        assert!(growth.step(0).unwrap().is_empty());
+        assert_eq!(growth.step(1).unwrap().len(), 1);
+
+        let sites = |network: &RoadNetwork| {
+            network
+                .nodes()
+                .iter()
+                .map(|node| (node.site.x, node.site.y))
+                .collect::<Vec<_>>()
+        };
+        while !growth.is_finished() {
+            let (previous, iterations) = (growth.network.clone(), growth.iterations());
+            let added = growth.step(2000).unwrap();
+            assert!(growth.is_finished() || growth.iterations() == iterations + 2000);
+
+            // the network is the one of the generation stopped at the same iteration
+            let partial = growth
+                .generator
+                .grow_roads(growth.iterations(), &Progress::default())
+                .unwrap();
+            let partial =
+                RoadNetwork::from_path_network(&partial.network, growth.generator.river_network());
+            assert_eq!(sites(&partial), sites(&growth.network));
+            assert_eq!(partial.paths().len(), growth.paths().len());
+
+            // nodes are only added, and the paths which are new are returned
+            assert!(sites(&growth.network).starts_with(&sites(&previous)));
+            assert_eq!(
+                added.len(),
+                growth
+                    .network
+                    .paths()
+                    .iter()
+                    .filter(|path| !previous
+                        .paths()
+                        .iter()
+                        .any(|other| (other.start, other.end) == (path.start, path.end)))
+                    .count()
+            );
+        }
+        assert!(growth.step(1).unwrap().is_empty());
+
+        // without satellites, the grown network is the one of the map
+        let dataset = include_str!("../../frontend/static/dataset/placenames.csv");
+        let standard = StandardMap::with_params(1, 1.4, dataset.into(), &params).unwrap();
+        let paths = growth.paths();
+        let expected = standard.network_paths();
+        assert_eq!(paths.len(), expected.len());
+        assert!(paths.iter().zip(&expected).all(|(a, b)| {
+            (a.node1_id(), a.node2_id(), a.is_bridge())
+                == (b.node1_id(), b.node2_id(), b.is_bridge())
+        }));
+
+        growth.reset();
+        assert!(growth.paths().is_empty());
+        assert_eq!(growth.iterations(), 0);
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// An isoline traced on a grid, in fractional grid indices.
+///
+/// Lines are oriented so that rings enclosing the values above the level have a negative
+/// [`signed_area`], i.e. they run clockwise when the y axis points up.
+pub(crate) struct Isoline {
+    pub points: Vec<(f64, f64)>,
+    pub closed: bool,
+}
+
+/// Crossing point of an isoline on an edge between two adjacent grid points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EdgeKey {
+    /// edge between (x, y) and (x + 1, y)
+    Horizontal(usize, usize),
+    /// edge between (x, y) and (x, y + 1)
+    Vertical(usize, usize),
+}
+
+/// Trace isolines of `level` on a row-major grid using marching squares.
+///
+/// Infinite values are treated as lying exactly on the grid point, so a grid padded with
+/// `f64::NEG_INFINITY` produces isolines that run along the padding.
+pub(crate) fn trace(values: &[f64], width: usize, height: usize, level: f64) -> Vec<Isoline> {
+    let value = |x: usize, y: usize| values[y * width + x];
+    let inside = |x: usize, y: usize| value(x, y) >= level;
+
+    let point_of = |key: EdgeKey| -> (f64, f64) {
+        let ((x0, y0), (x1, y1)) = match key {
+            EdgeKey::Horizontal(x, y) => ((x, y), (x + 1, y)),
+            EdgeKey::Vertical(x, y) => ((x, y), (x, y + 1)),
+        };
+        let (a, b) = (value(x0, y0), value(x1, y1));
+        let t = if a.is_infinite() {
+            0.0
+        } else if b.is_infinite() {
+            1.0
+        } else {
+            ((level - a) / (b - a)).clamp(0.0, 1.0)
+        };
+        (
+            x0 as f64 + (x1 as f64 - x0 as f64) * t,
+            y0 as f64 + (y1 as f64 - y0 as f64) * t,
+        )
+    };
+
+    let mut segments = Vec::new();
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width.saturating_sub(1) {
+            // corners and edges of the cell in clockwise order
+            let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+            let edges = [
+                EdgeKey::Horizontal(x, y),
+                EdgeKey::Vertical(x + 1, y),
+                EdgeKey::Horizontal(x, y + 1),
+                EdgeKey::Vertical(x, y),
+            ];
+
+            // (edge, whether the isoline enters the region above the level)
+            let crossings = (0..4)
+                .filter_map(|k| {
+                    let a = inside(corners[k].0, corners[k].1);
+                    let b = inside(corners[(k + 1) % 4].0, corners[(k + 1) % 4].1);
+                    (a != b).then_some((edges[k], b))
+                })
+                .collect::<Vec<_>>();
+
+            match crossings.len() {
+                2 => {
+                    let (start, end) = if crossings[0].1 {
+                        (crossings[0].0, crossings[1].0)
+                    } else {
+                        (crossings[1].0, crossings[0].0)
+                    };
+                    segments.push((start, end));
+                }
+                4 => {
+                    // saddle: decide the connectivity by the average of the corners
+                    let center = corners.iter().map(|&(cx, cy)| value(cx, cy)).sum::<f64>() / 4.0;
+                    let joined = center >= level;
+                    (0..4).filter(|&p| crossings[p].1).for_each(|p| {
+                        let partner = if joined { (p + 3) % 4 } else { (p + 1) % 4 };
+                        segments.push((crossings[p].0, crossings[partner].0));
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let next = segments.iter().copied().collect::<BTreeMap<_, _>>();
+    let has_incoming = segments
+        .iter()
+        .map(|(_, end)| *end)
+        .collect::<BTreeSet<_>>();
+
+    let mut visited = BTreeSet::new();
+    let mut isolines = Vec::new();
+
+    // open lines start at the border of the grid, so they are traced first
+    let open_starts = segments
+        .iter()
+        .map(|(start, _)| *start)
+        .filter(|start| !has_incoming.contains(start));
+    let closed_starts = segments.iter().map(|(start, _)| *start);
+
+    for (start, closed) in open_starts
+        .map(|start| (start, false))
+        .collect::<Vec<_>>()
+        .into_iter()
+        .chain(closed_starts.map(|start| (start, true)))
+    {
+        if visited.contains(&start) {
+            continue;
+        }
+        let mut points = vec![point_of(start)];
+        visited.insert(start);
+        let mut current = start;
+        while let Some(&following) = next.get(&current) {
+            points.push(point_of(following));
+            if following == start || !visited.insert(following) {
+                break;
+            }
+            current = following;
+        }
+        isolines.push(Isoline { points, closed });
+    }
+
+    isolines
+}
+
+/// Signed area of a ring (positive if counterclockwise with the y axis pointing up).
+pub(crate) fn signed_area(ring: &[(f64, f64)]) -> f64 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+        .sum::<f64>()
+        / 2.0
+}
+
+/// Whether the point is inside the ring (even-odd rule).
+pub(crate) fn ring_contains(ring: &[(f64, f64)], point: (f64, f64)) -> bool {
+    let mut inside = false;
+    for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+        if (a.1 > point.1) != (b.1 > point.1)
+            && point.0 < (b.0 - a.0) * (point.1 - a.1) / (b.1 - a.1) + a.0
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_closed_ring() {
+        let (width, height) = (5, 5);
+        let values = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f64 - 2.0, (i / width) as f64 - 2.0);
+                2.0 - (x * x + y * y).sqrt()
+            })
+            .collect::<Vec<_>>();
+
+        let isolines = trace(&values, width, height, 0.5);
+        assert_eq!(isolines.len(), 1);
+        let ring = &isolines[0];
+        assert!(ring.closed);
+        assert_eq!(ring.points.first(), ring.points.last());
+        // rings around the region above the level run clockwise
+        assert!(signed_area(&ring.points) < 0.0);
+        assert!(ring_contains(&ring.points, (2.0, 2.0)));
+        assert!(!ring_contains(&ring.points, (0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_trace_open_line() {
+        let (width, height) = (4, 3);
+        let values = (0..width * height)
+            .map(|i| (i % width) as f64)
+            .collect::<Vec<_>>();
+
+        let isolines = trace(&values, width, height, 1.5);
+        assert_eq!(isolines.len(), 1);
+        assert!(!isolines[0].closed);
+        assert_eq!(isolines[0].points.len(), 3);
+        assert!(isolines[0].points.iter().all(|p| p.0 == 1.5));
+    }
+}
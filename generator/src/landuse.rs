@@ -0,0 +1,326 @@
+use rayon::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::{buffer::ElevationBuffer, params::GenerationParams, standard_map::StandardMap};
+
+/// Land use of a pixel.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LandUse {
+    Sea = 0,
+    UrbanCore = 1,
+    Residential = 2,
+    Farmland = 3,
+    Forest = 4,
+    Wetland = 5,
+    Beach = 6,
+    BareMountain = 7,
+}
+
+/// Values of a pixel the land use is derived from.
+#[derive(Debug, Clone, Copy)]
+struct Pixel {
+    /// Elevation above the sea level.
+    height: f64,
+    /// Slope in radians.
+    slope: f64,
+    population_density: f64,
+    drainage_area: f64,
+    /// Distance to the nearest road.
+    road_distance: f64,
+}
+
+/// Thresholds of the land use, scaled with the parameters the map is generated with.
+#[derive(Debug, Clone, Copy)]
+struct Thresholds {
+    urban_density_min: f64,
+    residential_density_min: f64,
+    urban_road_distance_max: f64,
+    residential_road_distance_max: f64,
+    farmland_road_distance_max: f64,
+    beach_height_max: f64,
+    wetland_height_max: f64,
+    mountain_height_min: f64,
+    wetland_drainage_area_min: f64,
+}
+
+impl Thresholds {
+    fn new(params: &GenerationParams) -> Self {
+        Self {
+            urban_density_min: params.avenue_density_min * 15.0,
+            residential_density_min: params.avenue_density_min * 2.5,
+            urban_road_distance_max: params.path_normal_length * 2.0,
+            residential_road_distance_max: params.path_normal_length * 4.0,
+            farmland_road_distance_max: params.path_normal_length * 10.0,
+            // elevations grow with the size of the map
+            beach_height_max: params.bound * 0.002,
+            wetland_height_max: params.bound * 0.02,
+            mountain_height_min: params.bound * 0.14,
+            wetland_drainage_area_min: params.river_drainage_area_min / 3.0,
+        }
+    }
+}
+
+impl Pixel {
+    /// Land use of a pixel on the land.
+    fn land_use(&self, thresholds: &Thresholds) -> LandUse {
+        if self.population_density >= thresholds.urban_density_min
+            && self.road_distance <= thresholds.urban_road_distance_max
+        {
+            LandUse::UrbanCore
+        } else if self.population_density >= thresholds.residential_density_min
+            && self.road_distance <= thresholds.residential_road_distance_max
+        {
+            LandUse::Residential
+        } else if self.height < thresholds.beach_height_max && self.slope < 0.3 {
+            LandUse::Beach
+        } else if self.slope < 0.1
+            && self.height < thresholds.wetland_height_max
+            && self.drainage_area >= thresholds.wetland_drainage_area_min
+        {
+            LandUse::Wetland
+        } else if self.slope < 0.4 && self.road_distance <= thresholds.farmland_road_distance_max {
+            LandUse::Farmland
+        } else if self.height >= thresholds.mountain_height_min || self.slope >= 1.4 {
+            LandUse::BareMountain
+        } else {
+            LandUse::Forest
+        }
+    }
+}
+
+/// Land use of each pixel of an [`ElevationBuffer`], derived from the population density,
+/// the elevation, the slope, the drainage area and the distance to the roads.
+///
+/// The thresholds scale with the parameters, so pass the ones the map is generated with.
+#[wasm_bindgen]
+pub struct LandUseBuffer {
+    buffer: Vec<LandUse>,
+    image_width: u32,
+}
+
+#[wasm_bindgen]
+impl LandUseBuffer {
+    #[wasm_bindgen(constructor)]
+    pub fn from_map(
+        standard: &StandardMap,
+        elevation_buffer: &ElevationBuffer,
+        params: &GenerationParams,
+    ) -> LandUseBuffer {
+        let thresholds = Thresholds::new(params);
+        let (width, height) = elevation_buffer.dimensions();
+        let (bound_min, bound_max) = elevation_buffer.bounds();
+        let pixel_width = (bound_max.x - bound_min.x) / width as f64;
+        let pixel_height = (bound_max.y - bound_min.y) / height as f64;
+
+        // pixels the roads pass through
+        let mut roads = vec![false; width * height];
+        let step = pixel_width.min(pixel_height) * 0.5;
+        standard.network_paths().iter().for_each(|path| {
+            let (start, end) = (path.node1().site(), path.node2().site());
+            let length = (end.x - start.x).hypot(end.y - start.y);
+            let samples = (length / step).ceil() as usize + 1;
+            (0..=samples).for_each(|i| {
+                let t = i as f64 / samples as f64;
+                let x = ((start.x + (end.x - start.x) * t - bound_min.x) / pixel_width).floor();
+                let y = ((start.y + (end.y - start.y) * t - bound_min.y) / pixel_height).floor();
+                if x >= 0.0 && y >= 0.0 && (x as usize) < width && (y as usize) < height {
+                    roads[y as usize * width + x as usize] = true;
+                }
+            });
+        });
+        let road_distances = distance_transform(&roads, width, pixel_width, pixel_height);
+
+        let sea_level = standard.sea_level();
+        let elevations = elevation_buffer.as_slice();
+        let buffer = elevation_buffer
+            .slope()
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, slope)| {
+                let x = bound_min.x + pixel_width * ((i % width) as f64 + 0.5);
+                let y = bound_min.y + pixel_height * ((i / width) as f64 + 0.5);
+                let height = elevations[i] - sea_level;
+                if height < 0.0 {
+                    return LandUse::Sea;
+                }
+                Pixel {
+                    height,
+                    slope,
+                    population_density: standard.get_population_density(x, y),
+                    drainage_area: standard.get_drainage_area(x, y).unwrap_or(0.0),
+                    road_distance: road_distances[i],
+                }
+                .land_use(&thresholds)
+            })
+            .collect();
+
+        LandUseBuffer {
+            buffer,
+            image_width: width as u32,
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.image_width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.buffer
+            .len()
+            .checked_div(self.image_width as usize)
+            .unwrap_or(0) as u32
+    }
+
+    /// Land use of the pixel, or `undefined` outside the buffer.
+    pub fn get_land_use(&self, x: u32, y: u32) -> Option<LandUse> {
+        if x >= self.width() || y >= self.height() {
+            return None;
+        }
+        Some(self.buffer[y as usize * self.image_width as usize + x as usize])
+    }
+
+    /// The land use of each pixel as the values of [`LandUse`], in row-major order.
+    pub fn codes(&self) -> Vec<u8> {
+        self.buffer.iter().map(|&land_use| land_use as u8).collect()
+    }
+}
+
+impl LandUseBuffer {
+    /// The land use in row-major order.
+    pub fn as_slice(&self) -> &[LandUse] {
+        &self.buffer
+    }
+}
+
+/// Distance from each pixel to the nearest `true` pixel by the chamfer distance transform.
+///
+/// Pixels are `pixel_width` x `pixel_height` in size. The distance is infinite if there is no `true` pixel.
+fn distance_transform(
+    pixels: &[bool],
+    width: usize,
+    pixel_width: f64,
+    pixel_height: f64,
+) -> Vec<f64> {
+    let height = pixels.len().checked_div(width).unwrap_or(0);
+    let diagonal = pixel_width.hypot(pixel_height);
+    let mut distances = pixels
+        .iter()
+        .map(|&pixel| if pixel { 0.0 } else { f64::INFINITY })
+        .collect::<Vec<_>>();
+
+    // forward pass from the top left, then backward pass from the bottom right
+    let neighbors = [
+        (-1, 0, pixel_width),
+        (-1, -1, diagonal),
+        (0, -1, pixel_height),
+        (1, -1, diagonal),
+    ];
+    for backward in [false, true] {
+        for step in 0..width * height {
+            let i = if backward {
+                width * height - 1 - step
+            } else {
+                step
+            };
+            let (x, y) = ((i % width) as isize, (i / width) as isize);
+            for &(dx, dy, distance) in &neighbors {
+                let (dx, dy) = if backward { (-dx, -dy) } else { (dx, dy) };
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                    continue;
+                }
+                let candidate = distances[ny as usize * width + nx as usize] + distance;
+                if candidate < distances[i] {
+                    distances[i] = candidate;
+                }
+            }
+        }
+    }
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_transform() {
+        let mut pixels = vec![false; 5 * 3];
+        pixels[5 + 1] = true;
+        let distances = distance_transform(&pixels, 5, 1.0, 2.0);
+        assert_eq!(distances[5 + 1], 0.0);
+        assert_eq!(distances[5 + 4], 3.0);
+        assert_eq!(distances[1], 2.0);
+        assert_eq!(distances[4], 2.0 + 5f64.sqrt());
+
+        let distances = distance_transform(&[false; 4], 2, 1.0, 1.0);
+        assert!(distances.iter().all(|distance| distance.is_infinite()));
+    }
+
+    #[test]
+    fn test_land_use() {
+        let thresholds = Thresholds::new(&GenerationParams::default());
+        let plain = Pixel {
+            height: 3.0,
+            slope: 0.05,
+            population_density: 0.0,
+            drainage_area: 0.0,
+            road_distance: 3.0,
+        };
+        assert_eq!(plain.land_use(&thresholds), LandUse::Farmland);
+        let town = Pixel {
+            population_density: 0.5,
+            road_distance: 0.5,
+            ..plain
+        };
+        assert_eq!(town.land_use(&thresholds), LandUse::UrbanCore);
+        let marsh = Pixel {
+            drainage_area: 500.0,
+            ..plain
+        };
+        assert_eq!(marsh.land_use(&thresholds), LandUse::Wetland);
+        let hill = Pixel {
+            slope: 0.8,
+            road_distance: f64::INFINITY,
+            ..plain
+        };
+        assert_eq!(hill.land_use(&thresholds), LandUse::Forest);
+        let peak = Pixel {
+            height: 60.0,
+            ..hill
+        };
+        assert_eq!(peak.land_use(&thresholds), LandUse::BareMountain);
+
+        // the thresholds scale with the map
+        let larger = Thresholds::new(&GenerationParams {
+            bound: 500.0,
+            ..Default::default()
+        });
+        assert_eq!(peak.land_use(&larger), LandUse::Forest);
+        let wider = Thresholds::new(&GenerationParams {
+            path_normal_length: 0.1,
+            ..Default::default()
+        });
+        assert_eq!(plain.land_use(&wider), LandUse::Forest);
+    }
+
+    #[test]
+    fn test_get_land_use() {
+        let buffer = LandUseBuffer {
+            buffer: vec![
+                LandUse::Sea,
+                LandUse::Forest,
+                LandUse::Beach,
+                LandUse::Farmland,
+            ],
+            image_width: 2,
+        };
+        assert_eq!(buffer.get_land_use(1, 1), Some(LandUse::Farmland));
+        assert_eq!(buffer.get_land_use(2, 0), None);
+        assert_eq!(buffer.get_land_use(0, 2), None);
+    }
+}
@@ -1,15 +1,34 @@
+pub mod address;
+pub mod block;
 pub mod buffer;
+pub mod building;
+pub mod coastline;
+pub mod contour;
+pub mod error;
+pub mod export;
+pub mod growth;
+mod isoline;
+pub mod landuse;
 mod map;
+pub mod params;
 pub mod placename;
+pub mod poi;
+pub mod progress;
+pub mod render;
+mod snapshot;
 pub mod standard_map;
 pub mod types;
 
 #[cfg(test)]
 mod tests {
     use rand::Rng;
-    use tiny_skia::{Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+    use tiny_skia::{Paint, PathBuilder, Rect, Stroke, Transform};
 
-    use crate::{buffer::ElevationBuffer, standard_map::StandardMap};
+    use crate::{
+        buffer::{DensityBuffer, ElevationBuffer},
+        render::RenderStyle,
+        standard_map::StandardMap,
+    };
 
     #[test]
     fn test_standard_map() {
@@ -58,58 +77,8 @@ mod tests {
         );
         println!("人口 {}人", standard.get_population());
 
-        let blend_color = |color_a: [u8; 3], color_b: [u8; 3], prop: f64| -> [u8; 3] {
-            [
-                (color_a[0] as f64 + (color_b[0] as f64 - color_a[0] as f64) * prop) as u8,
-                (color_a[1] as f64 + (color_b[1] as f64 - color_a[1] as f64) * prop) as u8,
-                (color_a[2] as f64 + (color_b[2] as f64 - color_a[2] as f64) * prop) as u8,
-            ]
-        };
-
-        let get_color = |elevation: f64| -> [u8; 3] {
-            let colormap: [([u8; 3], f64); 6] = [
-                ([70, 150, 200], 0.0),
-                ([70, 150, 200], 0.05),
-                ([240, 240, 210], 0.125),
-                ([190, 200, 120], 0.5),
-                ([25, 100, 25], 25.0),
-                ([15, 60, 15], 40.0),
-            ];
-            let color_index = {
-                let mut i = 0;
-                while i < colormap.len() {
-                    if elevation < colormap[i].1 {
-                        break;
-                    }
-                    i += 1;
-                }
-                i
-            };
-
-            let land_color = if color_index == 0 {
-                colormap[0].0
-            } else if color_index == colormap.len() {
-                colormap[colormap.len() - 1].0
-            } else {
-                let color_a = colormap[color_index - 1];
-                let color_b = colormap[color_index];
-
-                let prop = (elevation - color_a.1) / (color_b.1 - color_a.1);
-                blend_color(color_a.0, color_b.0, prop)
-            };
-            land_color
-        };
-
         let elevation_buffer = &ElevationBuffer::from_terrain(standard, image_width, image_height);
 
-        let pixels = (0..image_width).flat_map(|imagex| {
-            (0..image_height).map(move |imagey| {
-                let elevation = elevation_buffer.get_elevation(imagex, imagey);
-                let color = get_color(elevation);
-                (imagex, imagey, color)
-            })
-        });
-
         let image_x_of = |x: f64| -> f64 {
             (x - standard.bound_min().x) / (standard.bound_max().x - standard.bound_min().x)
                 * image_width as f64
@@ -120,19 +89,11 @@ mod tests {
                 * image_height as f64
         };
 
-        let mut pixmap = Pixmap::new(image_width, image_height).unwrap();
+        let mut pixmap = RenderStyle::topographic()
+            .render_pixmap(elevation_buffer)
+            .unwrap();
         let mut paint = Paint::default();
 
-        for (imagex, imagey, color) in pixels {
-            paint.set_color_rgba8(color[0], color[1], color[2], 255);
-            pixmap.fill_rect(
-                Rect::from_xywh(imagex as f32, imagey as f32, 1.0, 1.0).unwrap(),
-                &paint,
-                Transform::identity(),
-                None,
-            );
-        }
-
         standard.network_paths().iter().for_each(|path| {
             let (inode, jnode) = (path.node1(), path.node2());
 
@@ -176,5 +137,39 @@ mod tests {
         );
 
         pixmap.save_png("test.png").unwrap();
+
+        let geojson: serde_json::Value = serde_json::from_str(&standard.to_geojson(None)).unwrap();
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(
+            features
+                .iter()
+                .filter(|feature| feature["properties"]["kind"] == "road")
+                .count(),
+            standard.network_paths().len()
+        );
+        assert!(features
+            .iter()
+            .any(|feature| feature["properties"]["kind"] == "origin"));
+
+        let densities = DensityBuffer::from_map(standard, 70, 50);
+        assert!(densities
+            .as_slice()
+            .iter()
+            .all(|density| (0.0..=1.0).contains(density)));
+        assert!(densities.as_slice().iter().any(|&density| density > 0.0));
+
+        let restored = StandardMap::from_bytes(&standard.to_bytes()).unwrap();
+        assert_eq!(restored.to_json(), standard.to_json());
+        assert_eq!(restored.to_geojson(None), standard.to_geojson(None));
+        let origin = standard.get_origin_site();
+        assert_eq!(
+            restored.get_elevation(origin.x, origin.y),
+            standard.get_elevation(origin.x, origin.y)
+        );
+        assert_eq!(
+            restored.get_population_density(origin.x, origin.y),
+            standard.get_population_density(origin.x, origin.y)
+        );
     }
 }
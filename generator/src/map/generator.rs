@@ -1,24 +1,46 @@
+use std::cell::Cell;
+
 use fastlem::{core::traits::Model, models::surface::terrain::Terrain2D};
 use naturalneighbor::Interpolator;
-use rand::SeedableRng;
 use street_engine::{
     core::{
+        container::path_network::PathNetwork,
         geometry::{angle::Angle, site::Site},
         Stage,
     },
     transport::{
         builder::TransportBuilder,
+        node::TransportNode,
         rules::TransportRules,
         traits::{RandomF64Provider, TransportRulesProvider},
     },
 };
 use terrain_graph::edge_attributed_undirected::EdgeAttributedUndirectedGraph;
 
+use crate::{
+    error::GenerationError,
+    progress::{GenerationPhase, Progress},
+};
+
 use super::{
+    railway::{Railway, RailwayConfig},
+    random::RandomStream,
+    river::RiverNetwork,
+    road::RoadNetwork,
+    route::{self, RouteCost},
     terrain::{TerrainBuilder, TerrainConfig},
-    Map,
+    Map, Settlement,
 };
 
+#[derive(Debug, Clone)]
+pub struct SettlementConfig {
+    /// Use this site instead of sampling one.
+    pub origin: Option<Site>,
+    pub city_size_prop: f64,
+    /// Scale of the population density around the settlement.
+    pub weight: f64,
+}
+
 #[derive(Debug, Clone)]
 pub struct MapConfig {
     pub sea_level: f64,
@@ -26,107 +48,228 @@ pub struct MapConfig {
     pub origin_sample_num: usize,
     pub max_retries: usize,
     pub origin_min_evelation: f64,
-    pub city_size_prop: f64,
+    pub river_drainage_area_min: f64,
+    pub path_normal_length: f64,
+    /// The first settlement is the main town, sampled from the central area of the map.
+    /// The others are satellites, which are skipped if no site is found for them.
+    pub settlements: Vec<SettlementConfig>,
+    pub settlement_distance_min: f64,
+    /// Climbing a unit of elevation costs highways as much as moving this distance.
+    pub highway_slope_penalty: f64,
+    /// Use this angle of the first road of the main town instead of a random one.
+    pub initial_angle: Option<f64>,
+    /// Lay no railway if `None`.
+    pub railway: Option<RailwayConfig>,
+}
+
+/// Road network grown from the origins of the settlements, before the highways between them.
+pub struct GrownRoads {
+    pub network: PathNetwork<TransportNode>,
+    /// Indices of the placed settlements, whose origins are the first nodes in this order.
+    pub placed: Vec<usize>,
+    pub initial_angle: f64,
+    /// Number of iterations which added roads.
+    pub iterations: usize,
+    /// Whether no candidates of roads are left.
+    pub finished: bool,
 }
 
 pub struct MapGenerator<TF>
 where
     TF: Fn(
-        f64,   // elevation
-        f64,   // population_density
-        Site,  // site
-        Angle, // angle
-        f64,   // slope
-        Stage, // stage
+        f64,           // elevation
+        f64,           // population_density
+        Site,          // site
+        Angle,         // angle
+        f64,           // slope
+        Stage,         // stage
+        Option<usize>, // order of the river crossed by the path
     ) -> Option<TransportRules>,
 {
     terrain: Terrain2D,
+    graph: EdgeAttributedUndirectedGraph<f64>,
+    /// Population densities of all the settlements merged.
     population_densities: Vec<f64>,
+    /// Population densities around each settlement.
+    settlement_densities: Vec<Vec<f64>>,
     interpolator: Interpolator,
+    river_network: RiverNetwork,
     map_config: MapConfig,
-    origin_site: Site,
+    seed: u32,
+    /// Settlements placed on the map, without their populations yet.
+    settlements: Vec<Settlement>,
     rules_fn: TF,
+    /// Number of calls of `get_rules`, to detect that `TransportBuilder` has no more candidates.
+    rules_calls: Cell<usize>,
 }
 
 impl<TF> MapGenerator<TF>
 where
-    TF: Fn(f64, f64, Site, Angle, f64, Stage) -> Option<TransportRules>,
+    TF: Fn(f64, f64, Site, Angle, f64, Stage, Option<usize>) -> Option<TransportRules>,
 {
     pub fn new(
         terrain_config: TerrainConfig,
         map_config: MapConfig,
         rules_fn: TF,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        println!("Creating terrain...");
-        let terrain_builder = TerrainBuilder::new(terrain_config.clone())?;
+        progress: &Progress,
+    ) -> Result<Self, GenerationError> {
+        let terrain_builder = TerrainBuilder::new(terrain_config.clone(), progress)?;
         let model = terrain_builder.get_model().clone();
-        let terrain = terrain_builder.build()?;
-
-        let mut rnd = RandomF64::new(rand::rngs::StdRng::seed_from_u64(0));
-
-        let central_bound_min = terrain_config.central_bound_min();
-        let central_bound_max = terrain_config.central_bound_max();
-
-        let mut origin_site = None;
-        for _ in 0..map_config.max_retries {
-            origin_site = (0..map_config.origin_sample_num)
-                .map(|_| {
-                    let x = rnd.gen_f64() * (central_bound_max.x - central_bound_min.x)
-                        + central_bound_min.x;
-                    let y = rnd.gen_f64() * (central_bound_max.y - central_bound_min.y)
-                        + central_bound_min.y;
-                    Site { x, y }
-                })
-                .filter_map(|site| {
-                    let elevation = terrain.get_elevation(&into_fastlem_site(site))?;
-                    if elevation < map_config.origin_min_evelation {
-                        return None;
-                    }
-                    Some((site, elevation))
-                })
-                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        let terrain = terrain_builder.build(progress)?;
 
-            if origin_site.is_some() {
-                break;
-            }
+        progress.report(GenerationPhase::PopulationDensity, Some(0.0))?;
+
+        let mut settlements = Vec::with_capacity(map_config.settlements.len());
+        let mut rnd = RandomF64::new(RandomStream::Settlements.rng(terrain_config.seed));
+        for (i, settlement) in map_config.settlements.iter().enumerate() {
+            let site = match settlement.origin {
+                Some(site) => {
+                    let elevation = terrain.get_elevation(&into_fastlem_site(site));
+                    if !elevation.is_some_and(|elevation| elevation >= map_config.sea_level) {
+                        return Err(GenerationError::InvalidOrigin);
+                    }
+                    site
+                }
+                None if i == 0 => sample_origin(&terrain, &terrain_config, &map_config)?,
+                None => {
+                    let sites = settlements
+                        .iter()
+                        .map(|(site, _)| *site)
+                        .collect::<Vec<_>>();
+                    match sample_satellite(&terrain, &terrain_config, &map_config, &sites, &mut rnd)
+                    {
+                        Some(site) => site,
+                        None => continue,
+                    }
+                }
+            };
+            settlements.push((site, settlement));
         }
-        let origin_site = origin_site.ok_or("Failed to find origin site")?.0;
 
-        let population_densities = calculate_population_density(
+        let (population_densities, settlement_densities) = calculate_population_density(
             &terrain,
-            origin_site,
+            &settlements,
             &terrain_config,
             model.graph(),
             &map_config,
         );
+        let settlements = settlements
+            .into_iter()
+            .map(|(site, settlement)| Settlement {
+                site,
+                city_size_prop: settlement.city_size_prop,
+                population: 0,
+            })
+            .collect::<Vec<_>>();
 
         let interpolator = Interpolator::new(terrain.sites());
+        progress.report(GenerationPhase::PopulationDensity, Some(1.0))?;
+
+        progress.report(GenerationPhase::RiverExtraction, Some(0.0))?;
+        let river_network = RiverNetwork::extract(
+            terrain.sites(),
+            terrain.elevations(),
+            model.areas(),
+            model.graph(),
+            map_config.sea_level,
+            map_config.river_drainage_area_min,
+        );
+        progress.report(GenerationPhase::RiverExtraction, Some(1.0))?;
 
         Ok(Self {
             terrain,
+            graph: model.graph().clone(),
             population_densities,
+            settlement_densities,
             interpolator,
+            river_network,
             map_config,
-            origin_site,
+            seed: terrain_config.seed,
+            settlements,
             rules_fn,
+            rules_calls: Cell::new(0),
         })
     }
 
-    pub fn build(self) -> Result<Map, Box<dyn std::error::Error>> {
-        let mut rnd = RandomF64::new(rand::rngs::StdRng::seed_from_u64(0));
+    pub fn river_network(&self) -> &RiverNetwork {
+        &self.river_network
+    }
 
-        let initial_angle = rnd.gen_f64() * std::f64::consts::PI * 2.0;
+    /// Grow the roads from the origins of the settlements for at most `iterations` iterations.
+    ///
+    /// The growth is deterministic, so the network after `k` iterations is the same
+    /// as the one in the middle of a longer growth.
+    pub fn grow_roads(
+        &self,
+        iterations: usize,
+        progress: &Progress,
+    ) -> Result<GrownRoads, GenerationError> {
+        let mut angles = RandomF64::new(RandomStream::InitialAngle.rng(self.seed));
+        let mut random_angle = || angles.gen_f64() * std::f64::consts::PI * 2.0;
+        let sampled_angle = random_angle();
+        let initial_angle = self.map_config.initial_angle.unwrap_or(sampled_angle);
+        let mut rnd = RandomF64::new(RandomStream::Roads.rng(self.seed));
 
-        println!("Creating transport network...");
-        let network = TransportBuilder::new(&self)
-            .add_origin(self.origin_site, initial_angle, None)
-            .ok_or("Failed to add origin")?
-            .iterate_as_possible(&mut rnd)
-            .build();
+        progress.report(GenerationPhase::RoadIteration, Some(0.0))?;
+        let mut builder = TransportBuilder::new(self)
+            .add_origin(self.settlements[0].site, initial_angle, None)
+            .ok_or(GenerationError::OriginInsertion)?;
+        let mut placed = vec![0];
+        for (i, settlement) in self.settlements.iter().enumerate().skip(1) {
+            let site = settlement.site;
+            let angle = random_angle();
+            // `add_origin` drops the builder when it fails, so check the site beforehand
+            if self
+                .get_rules(&site, Angle::new(angle), Stage::new(0))
+                .is_none()
+            {
+                continue;
+            }
+            builder = builder
+                .add_origin(site, angle, None)
+                .ok_or(GenerationError::OriginInsertion)?;
+            placed.push(i);
+        }
+        // Same as `iterate_as_possible`, but can be cancelled.
+        // An iteration with a candidate always queries the rules,
+        // so an iteration without queries means that no candidates are left.
+        let mut grown = 0;
+        let mut finished = false;
+        while grown < iterations {
+            let rules_calls = self.rules_calls.get();
+            builder = builder.iterate(&mut rnd);
+            if self.rules_calls.get() == rules_calls {
+                finished = true;
+                break;
+            }
+            grown += 1;
+            if grown % 100 == 0 {
+                progress.report(GenerationPhase::RoadIteration, None)?;
+            } else {
+                progress.check()?;
+            }
+        }
+        progress.report(GenerationPhase::RoadIteration, Some(1.0))?;
+
+        Ok(GrownRoads {
+            network: builder.build(),
+            placed,
+            initial_angle,
+            iterations: grown,
+            finished,
+        })
+    }
 
-        println!("Map generation completed.");
+    pub fn build(self, progress: &Progress) -> Result<Map, GenerationError> {
+        let GrownRoads {
+            network,
+            placed,
+            initial_angle,
+            ..
+        } = self.grow_roads(usize::MAX, progress)?;
 
-        let population = network
+        let mut populations = vec![0.0; placed.len()];
+        network
             .nodes_iter()
             .map(|(inode_id, inode)| {
                 let p = network.neighbors_iter(inode_id).map(|neighbors_iter| {
@@ -154,27 +297,142 @@ where
                         })
                         .sum::<f64>()
                 });
-                p.unwrap_or(0.0)
+                (inode.site, p.unwrap_or(0.0))
             })
-            .sum::<f64>()
-            * 50.0;
+            .for_each(|(site, p)| populations[self.nearest_settlement(&placed, site)] += p);
+
+        let mut network = RoadNetwork::from_path_network(&network, &self.river_network);
+        self.connect_settlements(&mut network, placed.len());
+
+        progress.report(GenerationPhase::Railway, Some(0.0))?;
+        let railway = match &self.map_config.railway {
+            Some(config) => Railway::build(
+                &self.terrain,
+                &self.graph,
+                &self.population_densities,
+                &placed
+                    .iter()
+                    .map(|&i| self.settlements[i].site)
+                    .collect::<Vec<_>>(),
+                self.map_config.sea_level,
+                config,
+            ),
+            None => Railway::default(),
+        };
+        progress.report(GenerationPhase::Railway, Some(1.0))?;
+
+        let settlements = placed
+            .iter()
+            .zip(populations)
+            .map(|(&i, population)| Settlement {
+                population: (population * 50.0) as usize,
+                ..self.settlements[i]
+            })
+            .collect::<Vec<_>>();
 
         Ok(Map::new(
             self.terrain,
             self.interpolator,
             network,
-            self.origin_site,
+            self.river_network,
+            self.population_densities,
+            self.settlements[0].site,
             initial_angle,
-            population as usize,
+            settlements
+                .iter()
+                .map(|settlement| settlement.population)
+                .sum(),
+            self.map_config.sea_level,
+            settlements,
+            railway,
         ))
     }
+
+    /// Index in `placed` of the settlement whose population density is the highest at `site`.
+    fn nearest_settlement(&self, placed: &[usize], site: Site) -> usize {
+        if placed.len() == 1 {
+            return 0;
+        }
+        placed
+            .iter()
+            .map(|&i| {
+                self.interpolator
+                    .interpolate(
+                        &self.settlement_densities[i],
+                        naturalneighbor::Point {
+                            x: site.x,
+                            y: site.y,
+                        },
+                    )
+                    .unwrap_or(None)
+                    .unwrap_or(0.0)
+            })
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0, |(index, _)| index)
+    }
+
+    /// Connect the settlements separated from the main town by highways over the terrain,
+    /// in the order of the settlements. Settlements across the sea are left separated.
+    fn connect_settlements(&self, network: &mut RoadNetwork, settlements_len: usize) {
+        let sites = self.terrain.sites();
+        let cost = RouteCost {
+            sea_level: self.map_config.sea_level,
+            slope_penalty: self.map_config.highway_slope_penalty,
+            max_gradient: f64::INFINITY,
+        };
+        for origin in 1..settlements_len {
+            let components = network.components();
+            if components[origin] == components[0] {
+                continue;
+            }
+            let origin_site = network.nodes()[origin].site;
+            let Some(target) = (0..network.nodes().len())
+                .filter(|&j| components[j] == components[0])
+                .min_by(|&a, &b| {
+                    let distance = |j: usize| network.nodes()[j].site.distance(&origin_site);
+                    distance(a).total_cmp(&distance(b))
+                })
+            else {
+                continue;
+            };
+            let target_site = network.nodes()[target].site;
+
+            let route = route::nearest_site(sites, into_fastlem_site(origin_site))
+                .zip(route::nearest_site(sites, into_fastlem_site(target_site)))
+                .and_then(|(start, goal)| {
+                    route::find_route(
+                        sites,
+                        self.terrain.elevations(),
+                        &self.graph,
+                        start,
+                        goal,
+                        cost,
+                    )
+                });
+            if let Some(route) = route {
+                // the ends of the route are replaced by the nodes themselves
+                let via = route
+                    .iter()
+                    .skip(1)
+                    .take(route.len().saturating_sub(2))
+                    .map(|&i| Site {
+                        x: sites[i].x,
+                        y: sites[i].y,
+                    })
+                    .collect::<Vec<_>>();
+                network.connect(origin, target, &via, &self.river_network);
+            }
+        }
+    }
 }
 
 impl<TF> TransportRulesProvider for MapGenerator<TF>
 where
-    TF: Fn(f64, f64, Site, Angle, f64, Stage) -> Option<TransportRules>,
+    TF: Fn(f64, f64, Site, Angle, f64, Stage, Option<usize>) -> Option<TransportRules>,
 {
     fn get_rules(&self, site: &Site, angle: Angle, stage: Stage) -> Option<TransportRules> {
+        self.rules_calls.set(self.rules_calls.get() + 1);
         let elevation = self.terrain.get_elevation(&into_fastlem_site(*site))?;
         let population_density = self
             .interpolator
@@ -196,6 +454,11 @@ where
             / slope_sample_distance;
         let slope_rad = slope.atan();
 
+        let river_crossing = self.river_network.crossing_order(
+            site.extend(angle.opposite(), self.map_config.path_normal_length),
+            *site,
+        );
+
         (self.rules_fn)(
             elevation,
             population_density,
@@ -203,6 +466,7 @@ where
             angle,
             slope_rad,
             stage,
+            river_crossing,
         )
     }
 }
@@ -223,20 +487,93 @@ impl<R: rand::Rng> RandomF64Provider for RandomF64<R> {
     }
 }
 
-fn into_fastlem_site(site: Site) -> fastlem::models::surface::sites::Site2D {
+pub(super) fn into_fastlem_site(site: Site) -> fastlem::models::surface::sites::Site2D {
     fastlem::models::surface::sites::Site2D {
         x: site.x,
         y: site.y,
     }
 }
 
+/// Sample the origin from the central area of the map, preferring lower sites above `origin_min_evelation`.
+fn sample_origin(
+    terrain: &Terrain2D,
+    terrain_config: &TerrainConfig,
+    map_config: &MapConfig,
+) -> Result<Site, GenerationError> {
+    let mut rnd = RandomF64::new(RandomStream::Origin.rng(terrain_config.seed));
+
+    let central_bound_min = terrain_config.central_bound_min();
+    let central_bound_max = terrain_config.central_bound_max();
+
+    for _ in 0..map_config.max_retries {
+        let origin_site = (0..map_config.origin_sample_num)
+            .map(|_| {
+                let x = rnd.gen_f64() * (central_bound_max.x - central_bound_min.x)
+                    + central_bound_min.x;
+                let y = rnd.gen_f64() * (central_bound_max.y - central_bound_min.y)
+                    + central_bound_min.y;
+                Site { x, y }
+            })
+            .filter_map(|site| {
+                let elevation = terrain.get_elevation(&into_fastlem_site(site))?;
+                if elevation < map_config.origin_min_evelation {
+                    return None;
+                }
+                Some((site, elevation))
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        if let Some((site, _)) = origin_site {
+            return Ok(site);
+        }
+    }
+    Err(GenerationError::OriginNotFound)
+}
+
+/// Sample a satellite from the whole map, away from the other settlements.
+/// Returns `None` if no site is found, e.g. when the land is too small.
+fn sample_satellite<R: rand::Rng>(
+    terrain: &Terrain2D,
+    terrain_config: &TerrainConfig,
+    map_config: &MapConfig,
+    settlement_sites: &[Site],
+    rnd: &mut RandomF64<R>,
+) -> Option<Site> {
+    let bound_min = terrain_config.bound_min();
+    let bound_max = terrain_config.bound_max();
+
+    (0..map_config.max_retries).find_map(|_| {
+        (0..map_config.origin_sample_num)
+            .map(|_| {
+                let x = rnd.gen_f64() * (bound_max.x - bound_min.x) + bound_min.x;
+                let y = rnd.gen_f64() * (bound_max.y - bound_min.y) + bound_min.y;
+                Site { x, y }
+            })
+            .filter(|site| {
+                settlement_sites
+                    .iter()
+                    .all(|other| other.distance(site) >= map_config.settlement_distance_min)
+            })
+            .filter_map(|site| {
+                let elevation = terrain.get_elevation(&into_fastlem_site(site))?;
+                if elevation < map_config.origin_min_evelation {
+                    return None;
+                }
+                Some((site, elevation))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(site, _)| site)
+    })
+}
+
+/// Population densities merged from all the settlements, and those around each settlement.
 fn calculate_population_density(
     terrain: &Terrain2D,
-    origin_site: Site,
+    settlements: &[(Site, &SettlementConfig)],
     terrain_config: &TerrainConfig,
     graph: &EdgeAttributedUndirectedGraph<f64>,
     map_config: &MapConfig,
-) -> Vec<f64> {
+) -> (Vec<f64>, Vec<Vec<f64>>) {
     let slopes = (0..terrain.sites().len())
         .map(|i| {
             let slopes = graph
@@ -253,33 +590,48 @@ fn calculate_population_density(
         })
         .collect::<Vec<_>>();
 
-    let densities = (0..terrain.sites().len())
+    let livable_densities = (0..terrain.sites().len())
         .map(|i| {
             let elevation = terrain.elevations()[i];
             if elevation < map_config.sea_level {
-                return (i, 0.0);
+                return 0.0;
             }
             let slope_sum = slopes[i].iter().fold(0.0, |acc, slope| acc + slope.abs());
             let slope_avg = slope_sum.abs() / slopes[i].len() as f64;
-            let density = (1.0 - slope_avg / map_config.max_slope_livable)
-                .max(0.0)
-                .min(1.0);
-            (i, density)
+            (1.0 - slope_avg / map_config.max_slope_livable).clamp(0.0, 1.0)
+        })
+        .collect::<Vec<_>>();
+
+    let settlement_densities = settlements
+        .iter()
+        .map(|(origin_site, settlement)| {
+            livable_densities
+                .iter()
+                .zip(terrain.sites())
+                .map(|(density, site)| {
+                    // distance between origin and site
+                    let distance = origin_site.distance(&Site {
+                        x: site.x,
+                        y: site.y,
+                    });
+                    let dprop = (1.0 - distance / terrain_config.bound_value())
+                        .clamp(0.0, 1.0)
+                        .powf(1.0 / settlement.city_size_prop);
+                    density * dprop * settlement.weight
+                })
+                .collect::<Vec<_>>()
         })
-        .map(|(i, density)| {
-            // distance between origin and site
-            let site = terrain.sites()[i];
-            let distance = origin_site.distance(&Site {
-                x: site.x,
-                y: site.y,
-            });
-            let dprop = (1.0 - distance / terrain_config.bound_value())
-                .min(1.0)
-                .max(0.0)
-                .powf(1.0 / map_config.city_size_prop);
-            density * dprop
+        .collect::<Vec<_>>();
+
+    let densities = (0..terrain.sites().len())
+        .map(|i| {
+            settlement_densities
+                .iter()
+                .map(|densities| densities[i])
+                .reduce(f64::max)
+                .unwrap_or(0.0)
         })
         .collect::<Vec<_>>();
 
-    densities
+    (densities, settlement_densities)
 }
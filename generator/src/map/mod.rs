@@ -1,38 +1,77 @@
 pub mod generator;
+pub mod railway;
+pub mod random;
+pub mod river;
+pub mod road;
+pub mod route;
 pub mod terrain;
 
 use fastlem::models::surface::terrain::Terrain2D;
 use naturalneighbor::Interpolator;
-use street_engine::{
-    core::{container::path_network::PathNetwork, geometry::site::Site},
-    transport::node::TransportNode,
-};
+use street_engine::core::geometry::site::Site;
+
+use self::{railway::Railway, river::RiverNetwork, road::RoadNetwork};
+
+/// A town or a village of the map.
+#[derive(Debug, Clone, Copy)]
+pub struct Settlement {
+    pub site: Site,
+    pub city_size_prop: f64,
+    pub population: usize,
+}
 
 pub struct Map {
     pub terrain: Terrain2D,
     pub interpolator: Interpolator,
-    pub network: PathNetwork<TransportNode>,
+    pub network: RoadNetwork,
+    pub river_network: RiverNetwork,
+    /// Population density at each site of the terrain, merged from all the settlements.
+    pub population_densities: Vec<f64>,
     pub origin: Site,
     pub initial_angle: f64,
+    /// Total population of the settlements.
     pub population: usize,
+    pub sea_level: f64,
+    /// Settlements, starting with the main town at the origin.
+    pub settlements: Vec<Settlement>,
+    pub railway: Railway,
 }
 
 impl Map {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         terrain: Terrain2D,
         interpolator: Interpolator,
-        network: PathNetwork<TransportNode>,
+        network: RoadNetwork,
+        river_network: RiverNetwork,
+        population_densities: Vec<f64>,
         origin: Site,
         initial_angle: f64,
         population: usize,
+        sea_level: f64,
+        settlements: Vec<Settlement>,
+        railway: Railway,
     ) -> Self {
         Self {
             terrain,
             interpolator,
             network,
+            river_network,
+            population_densities,
             origin,
             initial_angle,
             population,
+            sea_level,
+            settlements,
+            railway,
         }
     }
+
+    /// Population density at the given point, or 0 outside the terrain.
+    pub fn population_density_at(&self, x: f64, y: f64) -> f64 {
+        self.interpolator
+            .interpolate(&self.population_densities, naturalneighbor::Point { x, y })
+            .unwrap_or(None)
+            .unwrap_or(0.0)
+    }
 }
@@ -0,0 +1,155 @@
+use fastlem::models::surface::terrain::Terrain2D;
+use street_engine::core::geometry::site::Site;
+use terrain_graph::edge_attributed_undirected::EdgeAttributedUndirectedGraph;
+
+use super::{
+    generator::into_fastlem_site,
+    route::{self, RouteCost},
+};
+
+#[derive(Debug, Clone)]
+pub struct RailwayConfig {
+    /// Steepest slope of the line, in radians.
+    pub max_slope: f64,
+    /// Climbing a unit of elevation costs as much as moving this distance.
+    pub slope_penalty: f64,
+    /// Minimum distance between stations along the line.
+    pub station_distance_min: f64,
+    /// Stations are placed only where the population density is at least this.
+    pub station_density_min: f64,
+}
+
+/// A railway line with its stations.
+#[derive(Debug, Clone, Default)]
+pub struct Railway {
+    /// Sites of the line from west to east. Empty if no line is laid.
+    pub sites: Vec<Site>,
+    /// Indices of the sites with stations, from west to east.
+    pub stations: Vec<usize>,
+}
+
+impl Railway {
+    /// Lay a line across the land through the settlements, avoiding the sea and steep slopes.
+    ///
+    /// The line runs between the westernmost and the easternmost sites reachable from the main town
+    /// (the first settlement). Settlements which cannot be reached are passed by.
+    pub fn build(
+        terrain: &Terrain2D,
+        graph: &EdgeAttributedUndirectedGraph<f64>,
+        population_densities: &[f64],
+        settlement_sites: &[Site],
+        sea_level: f64,
+        config: &RailwayConfig,
+    ) -> Self {
+        let sites = terrain.sites();
+        let elevations = terrain.elevations();
+        let cost = RouteCost {
+            sea_level,
+            slope_penalty: config.slope_penalty,
+            max_gradient: config.max_slope.tan(),
+        };
+
+        let mut settlements = settlement_sites
+            .iter()
+            .filter_map(|site| route::nearest_site(sites, into_fastlem_site(*site)))
+            .collect::<Vec<_>>();
+        let Some(&main_town) = settlements.first() else {
+            return Self::default();
+        };
+        let reached = route::reachable(elevations, graph, main_town, cost);
+        let reached_x = || {
+            (0..sites.len())
+                .filter(|&i| reached[i])
+                .map(|i| (i, sites[i].x))
+        };
+        let (Some((west, _)), Some((east, _))) = (
+            reached_x().min_by(|a, b| a.1.total_cmp(&b.1)),
+            reached_x().max_by(|a, b| a.1.total_cmp(&b.1)),
+        ) else {
+            return Self::default();
+        };
+
+        settlements.retain(|&i| reached[i]);
+        settlements.sort_by(|&a, &b| sites[a].x.total_cmp(&sites[b].x));
+        let mut waypoints = std::iter::once(west)
+            .chain(settlements)
+            .chain(std::iter::once(east))
+            .collect::<Vec<_>>();
+        waypoints.dedup();
+        if waypoints.len() < 2 {
+            return Self::default();
+        }
+
+        // every waypoint is reachable, so each leg is found
+        let mut line = vec![west];
+        for leg in waypoints.windows(2) {
+            if let Some(route) = route::find_route(sites, elevations, graph, leg[0], leg[1], cost) {
+                line.extend(route.into_iter().skip(1));
+            }
+        }
+
+        let line_sites = line
+            .iter()
+            .map(|&i| Site {
+                x: sites[i].x,
+                y: sites[i].y,
+            })
+            .collect::<Vec<_>>();
+        let densities = line
+            .iter()
+            .map(|&i| population_densities[i])
+            .collect::<Vec<_>>();
+        let stations = place_stations(&line_sites, &densities, config);
+
+        Self {
+            sites: line_sites,
+            stations,
+        }
+    }
+}
+
+/// Place stations along the line, from the densest sites, keeping them apart.
+fn place_stations(line: &[Site], densities: &[f64], config: &RailwayConfig) -> Vec<usize> {
+    let positions = std::iter::once(0.0)
+        .chain(line.windows(2).scan(0.0, |position, pair| {
+            *position += pair[0].distance(&pair[1]);
+            Some(*position)
+        }))
+        .collect::<Vec<f64>>();
+
+    let mut candidates = (0..line.len())
+        .filter(|&i| densities[i] >= config.station_density_min)
+        .collect::<Vec<_>>();
+    candidates.sort_by(|&a, &b| densities[b].total_cmp(&densities[a]).then(a.cmp(&b)));
+
+    let mut stations: Vec<usize> = Vec::new();
+    for candidate in candidates {
+        if stations.iter().all(|&station| {
+            (positions[station] - positions[candidate]).abs() >= config.station_distance_min
+        }) {
+            stations.push(candidate);
+        }
+    }
+    stations.sort();
+    stations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_stations() {
+        let line = (0..10)
+            .map(|i| Site::new(i as f64, 0.0))
+            .collect::<Vec<_>>();
+        let densities = [0.0, 0.2, 0.5, 0.3, 0.0, 0.0, 0.1, 0.4, 0.35, 0.0];
+        let config = RailwayConfig {
+            max_slope: 0.1,
+            slope_penalty: 0.0,
+            station_distance_min: 3.0,
+            station_density_min: 0.15,
+        };
+        assert_eq!(place_stations(&line, &densities, &config), vec![2, 7]);
+    }
+}
@@ -0,0 +1,31 @@
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Independent random streams derived from the seed of a map.
+///
+/// Each stream has its own generator, so adding draws to one stream
+/// does not change the results of the others.
+#[derive(Debug, Clone, Copy)]
+pub enum RandomStream {
+    Sites = 1,
+    Origin = 2,
+    InitialAngle = 3,
+    Roads = 4,
+    Settlements = 5,
+    SettlementSizes = 6,
+    Streets = 7,
+    Buildings = 8,
+    Pois = 9,
+}
+
+impl RandomStream {
+    pub fn rng(self, seed: u32) -> StdRng {
+        StdRng::seed_from_u64(splitmix64(((seed as u64) << 8) | self as u64))
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use fastlem::models::surface::sites::Site2D;
+use street_engine::core::geometry::{line_segment::LineSegment, site::Site};
+use terrain_graph::edge_attributed_undirected::EdgeAttributedUndirectedGraph;
+
+/// A polyline of a river between two confluences (or a source / a mouth).
+///
+/// `order` is the Strahler order of the segment.
+#[derive(Debug, Clone)]
+pub struct River {
+    pub sites: Vec<Site>,
+    pub order: usize,
+}
+
+pub struct RiverNetwork {
+    pub drainage_areas: Vec<f64>,
+    pub rivers: Vec<River>,
+    segment_grid: SegmentGrid,
+}
+
+impl RiverNetwork {
+    pub fn new(drainage_areas: Vec<f64>, rivers: Vec<River>) -> Self {
+        let segment_grid = SegmentGrid::new(&rivers);
+        Self {
+            drainage_areas,
+            rivers,
+            segment_grid,
+        }
+    }
+
+    /// Route the flow on the terrain graph and extract rivers.
+    ///
+    /// Each site drains into its steepest downhill neighbor. Sites whose accumulated
+    /// drainage area is at least `drainage_area_min` and which are above `sea_level` become river sites.
+    pub fn extract(
+        sites: &[Site2D],
+        elevations: &[f64],
+        areas: &[f64],
+        graph: &EdgeAttributedUndirectedGraph<f64>,
+        sea_level: f64,
+        drainage_area_min: f64,
+    ) -> Self {
+        let num = sites.len();
+
+        // the site to which each site drains (itself if it is a sink)
+        let receivers = (0..num)
+            .map(|i| {
+                if elevations[i] < sea_level {
+                    return i;
+                }
+                graph
+                    .neighbors_of(i)
+                    .iter()
+                    .filter(|(j, _)| elevations[*j] < elevations[i])
+                    .map(|(j, distance)| (*j, (elevations[i] - elevations[*j]) / distance))
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(j, _)| j)
+                    .unwrap_or(i)
+            })
+            .collect::<Vec<_>>();
+
+        // from the highest to the lowest
+        let mut order = (0..num).collect::<Vec<_>>();
+        order.sort_by(|a, b| elevations[*b].total_cmp(&elevations[*a]));
+
+        let mut drainage_areas = areas.to_vec();
+        order.iter().for_each(|&i| {
+            let j = receivers[i];
+            if j != i {
+                drainage_areas[j] += drainage_areas[i];
+            }
+        });
+
+        let is_river = (0..num)
+            .map(|i| {
+                elevations[i] >= sea_level
+                    && receivers[i] != i
+                    && drainage_areas[i] >= drainage_area_min
+            })
+            .collect::<Vec<_>>();
+
+        let mut donors = vec![0; num];
+        let mut strahler = vec![0; num];
+        let mut max_donor_order = vec![(0, 0); num];
+        order.iter().filter(|&&i| is_river[i]).for_each(|&i| {
+            let (max, count) = max_donor_order[i];
+            strahler[i] = match count {
+                0 => 1,
+                1 => max,
+                _ => max + 1,
+            };
+            let j = receivers[i];
+            donors[j] += 1;
+            let (max, count) = max_donor_order[j];
+            max_donor_order[j] = if strahler[i] > max {
+                (strahler[i], 1)
+            } else if strahler[i] == max {
+                (max, count + 1)
+            } else {
+                (max, count)
+            };
+        });
+
+        let to_site = |i: usize| Site {
+            x: sites[i].x,
+            y: sites[i].y,
+        };
+
+        // a river is split at sources and confluences
+        let rivers = order
+            .iter()
+            .filter(|&&i| is_river[i] && donors[i] != 1)
+            .map(|&head| {
+                let mut path = vec![to_site(head)];
+                let mut i = head;
+                loop {
+                    let j = receivers[i];
+                    path.push(to_site(j));
+                    if !is_river[j] || donors[j] != 1 {
+                        break;
+                    }
+                    i = j;
+                }
+                River {
+                    sites: path,
+                    order: strahler[head],
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Self::new(drainage_areas, rivers)
+    }
+
+    /// Returns the highest Strahler order among the rivers crossed by the segment from `start` to `end`.
+    pub fn crossing_order(&self, start: Site, end: Site) -> Option<usize> {
+        let line = LineSegment::new(start, end);
+        self.segment_grid
+            .segments_around(start, end)
+            .filter_map(|(river_index, segment_index)| {
+                let river = &self.rivers[river_index];
+                let segment =
+                    LineSegment::new(river.sites[segment_index], river.sites[segment_index + 1]);
+                line.get_intersection(&segment).map(|_| river.order)
+            })
+            .max()
+    }
+}
+
+/// Uniform grid of river segments to find crossings without scanning all rivers.
+struct SegmentGrid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<(usize, usize)>>,
+}
+
+impl SegmentGrid {
+    fn new(rivers: &[River]) -> Self {
+        let mut grid = Self {
+            cell_size: 2.0,
+            cells: HashMap::new(),
+        };
+        rivers.iter().enumerate().for_each(|(river_index, river)| {
+            river.sites.windows(2).enumerate().for_each(|(i, segment)| {
+                grid.cells_around(segment[0], segment[1])
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .for_each(|cell| {
+                        grid.cells.entry(cell).or_default().push((river_index, i));
+                    });
+            });
+        });
+        grid
+    }
+
+    fn cells_around(&self, start: Site, end: Site) -> impl Iterator<Item = (i64, i64)> {
+        let cell_of = |v: f64| (v / self.cell_size).floor() as i64;
+        let (x0, x1) = (cell_of(start.x.min(end.x)), cell_of(start.x.max(end.x)));
+        let (y0, y1) = (cell_of(start.y.min(end.y)), cell_of(start.y.max(end.y)));
+        (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+    }
+
+    fn segments_around(&self, start: Site, end: Site) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.cells_around(start, end)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strahler_order() {
+        // 0   1   2   3
+        //  \ /     \ /
+        //   4       5
+        //    \     /
+        //       6
+        //       |
+        //       7 (sea)
+        let sites = [
+            (0.0, 3.0),
+            (2.0, 3.0),
+            (4.0, 3.0),
+            (6.0, 3.0),
+            (1.0, 2.0),
+            (5.0, 2.0),
+            (3.0, 1.0),
+            (3.0, 0.0),
+        ]
+        .map(|(x, y)| Site2D { x, y });
+        let elevations = [10.0, 10.0, 10.0, 10.0, 5.0, 5.0, 2.0, -1.0];
+        let mut graph = EdgeAttributedUndirectedGraph::new(sites.len());
+        [(0, 4), (1, 4), (2, 5), (3, 5), (4, 6), (5, 6), (6, 7)]
+            .iter()
+            .for_each(|&(i, j)| graph.add_edge(i, j, 1.0));
+
+        let network = RiverNetwork::extract(&sites, &elevations, &[1.0; 8], &graph, 0.0, 1.0);
+
+        assert_eq!(network.drainage_areas[6], 7.0);
+        assert_eq!(network.drainage_areas[7], 8.0);
+
+        let mut orders = network
+            .rivers
+            .iter()
+            .map(|river| (river.sites.len(), river.order))
+            .collect::<Vec<_>>();
+        orders.sort();
+        assert_eq!(
+            orders,
+            vec![(2, 1), (2, 1), (2, 1), (2, 1), (2, 2), (2, 2), (2, 3)]
+        );
+
+        let crossing =
+            |x0, y0, x1, y1| network.crossing_order(Site { x: x0, y: y0 }, Site { x: x1, y: y1 });
+        assert_eq!(crossing(2.0, 0.5, 4.0, 0.5), Some(3));
+        assert_eq!(crossing(0.0, 2.0, 6.0, 2.0), Some(2));
+        assert_eq!(crossing(0.0, 2.6, 0.4, 2.6), Some(1));
+        assert_eq!(crossing(5.0, 0.0, 6.0, 1.0), None);
+    }
+}
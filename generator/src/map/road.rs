@@ -0,0 +1,497 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    f64::consts::PI,
+};
+
+use street_engine::{
+    core::{
+        container::path_network::PathNetwork,
+        geometry::{line_segment::LineSegment, site::Site},
+        Stage,
+    },
+    transport::node::TransportNode,
+};
+
+use super::river::RiverNetwork;
+use crate::isoline::signed_area;
+
+/// Distance within which two sites are the same point when splitting paths at crossings.
+const CROSSING_EPSILON: f64 = 1e-9;
+
+/// A path between two nodes of [`RoadNetwork`] (`start < end`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoadPath {
+    pub start: usize,
+    pub end: usize,
+    /// Whether the path crosses a river.
+    pub is_bridge: bool,
+}
+
+/// Road network of a map.
+///
+/// Unlike `PathNetwork`, the network can be rebuilt from its nodes and paths,
+/// so it can be saved and restored. Nodes are indexed in the order of creation.
+#[derive(Debug, Clone, Default)]
+pub struct RoadNetwork {
+    nodes: Vec<TransportNode>,
+    paths: Vec<RoadPath>,
+    /// Nodes connected to each node by a path.
+    neighbors: Vec<Vec<usize>>,
+}
+
+impl RoadNetwork {
+    pub fn new(nodes: Vec<TransportNode>, paths: Vec<RoadPath>) -> Self {
+        let mut neighbors = vec![Vec::new(); nodes.len()];
+        paths.iter().for_each(|path| {
+            neighbors[path.start].push(path.end);
+            neighbors[path.end].push(path.start);
+        });
+        Self {
+            nodes,
+            paths,
+            neighbors,
+        }
+    }
+
+    /// Copy the network built by `TransportBuilder`, flagging the paths crossing the rivers.
+    pub fn from_path_network(
+        network: &PathNetwork<TransportNode>,
+        river_network: &RiverNetwork,
+    ) -> Self {
+        let indices = network
+            .nodes_iter()
+            .enumerate()
+            .map(|(index, (node_id, _))| (node_id, index))
+            .collect::<BTreeMap<_, _>>();
+        let indices = &indices;
+
+        let nodes = network
+            .nodes_iter()
+            .map(|(_, node)| *node)
+            .collect::<Vec<_>>();
+
+        let paths = network
+            .nodes_iter()
+            .flat_map(|(inode_id, _)| {
+                network
+                    .neighbors_iter(inode_id)
+                    .into_iter()
+                    .flatten()
+                    .filter(move |(jnode_id, _)| inode_id < *jnode_id)
+                    .map(move |(jnode_id, _)| (indices[&inode_id], indices[&jnode_id]))
+            })
+            .map(|(start, end)| RoadPath {
+                start,
+                end,
+                is_bridge: river_network
+                    .crossing_order(nodes[start].site, nodes[end].site)
+                    .is_some(),
+            })
+            .collect();
+
+        Self::new(nodes, paths)
+    }
+
+    pub fn nodes(&self) -> &[TransportNode] {
+        &self.nodes
+    }
+
+    pub fn paths(&self) -> &[RoadPath] {
+        &self.paths
+    }
+
+    /// Nodes connected to `node` by a path, or an empty list if there is no such node.
+    pub fn neighbors(&self, node: usize) -> &[usize] {
+        self.neighbors.get(node).map_or(&[], Vec::as_slice)
+    }
+
+    /// Stage of a path, which is the larger stage of its nodes.
+    pub fn path_stage(&self, path: &RoadPath) -> usize {
+        self.nodes[path.start]
+            .stage
+            .as_num()
+            .max(self.nodes[path.end].stage.as_num())
+    }
+
+    /// Group the paths into streets, which are chains of paths continuing straight through the nodes.
+    ///
+    /// Main roads (stage 0) and local roads are not mixed, and a street turns by `max_turn` radians at most
+    /// at a node. Returns the nodes of each street in order. Every path belongs to exactly one street.
+    pub fn streets(&self, max_turn: f64) -> Vec<Vec<usize>> {
+        let mut incident = vec![Vec::new(); self.nodes.len()];
+        self.paths.iter().enumerate().for_each(|(i, path)| {
+            incident[path.start].push(i);
+            incident[path.end].push(i);
+        });
+        let other = |path: usize, node: usize| {
+            let path = &self.paths[path];
+            if path.start == node {
+                path.end
+            } else {
+                path.start
+            }
+        };
+        let direction = |path: usize, node: usize| {
+            let (from, to) = (self.nodes[node].site, self.nodes[other(path, node)].site);
+            (to.y - from.y).atan2(to.x - from.x)
+        };
+
+        // the path continuing straight from a path through a node
+        let mut links = HashMap::new();
+        for (node, paths) in incident.iter().enumerate() {
+            let mut pairs = Vec::new();
+            for (i, &a) in paths.iter().enumerate() {
+                for &b in &paths[i + 1..] {
+                    let (stage_a, stage_b) = (
+                        self.path_stage(&self.paths[a]),
+                        self.path_stage(&self.paths[b]),
+                    );
+                    if (stage_a == 0) != (stage_b == 0) {
+                        continue;
+                    }
+                    let angle = (direction(a, node) - direction(b, node)).rem_euclid(PI * 2.0);
+                    let turn = (angle - PI).abs();
+                    if turn <= max_turn {
+                        pairs.push((turn, a, b));
+                    }
+                }
+            }
+            pairs.sort_by(|x, y| x.0.total_cmp(&y.0));
+            for (_, a, b) in pairs {
+                if !links.contains_key(&(a, node)) && !links.contains_key(&(b, node)) {
+                    links.insert((a, node), b);
+                    links.insert((b, node), a);
+                }
+            }
+        }
+
+        let mut visited = vec![false; self.paths.len()];
+        let mut streets = Vec::new();
+        for start in 0..self.paths.len() {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut walk = |node: usize| {
+                let (mut path, mut node) = (start, node);
+                let mut nodes = Vec::new();
+                while let Some(&next) = links.get(&(path, node)) {
+                    if visited[next] {
+                        break;
+                    }
+                    visited[next] = true;
+                    node = other(next, node);
+                    nodes.push(node);
+                    path = next;
+                }
+                nodes
+            };
+            let forward = walk(self.paths[start].end);
+            let mut street = walk(self.paths[start].start);
+            street.reverse();
+            street.extend([self.paths[start].start, self.paths[start].end]);
+            street.extend(forward);
+            streets.push(street);
+        }
+        streets
+    }
+
+    /// Faces of the network as a planar graph, which are the blocks enclosed by the roads.
+    ///
+    /// Returns the nodes around each face counterclockwise (with the y axis pointing up),
+    /// without repeating the first one. Dead ends are pruned beforehand, and the unbounded face
+    /// around each connected component is left out. A component inside a face is not cut out of it.
+    pub fn faces(&self) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![Vec::new(); self.nodes.len()];
+        self.paths.iter().for_each(|path| {
+            if path.start != path.end {
+                neighbors[path.start].push(path.end);
+                neighbors[path.end].push(path.start);
+            }
+        });
+        neighbors.iter_mut().for_each(|list| {
+            list.sort();
+            list.dedup();
+        });
+
+        // dead ends do not enclose anything
+        let mut leaves = (0..self.nodes.len())
+            .filter(|&i| neighbors[i].len() == 1)
+            .collect::<Vec<_>>();
+        while let Some(leaf) = leaves.pop() {
+            let Some(&next) = neighbors[leaf].first() else {
+                continue;
+            };
+            neighbors[leaf].clear();
+            neighbors[next].retain(|&i| i != leaf);
+            if neighbors[next].len() == 1 {
+                leaves.push(next);
+            }
+        }
+
+        let sites = self.nodes.iter().map(|node| node.site).collect::<Vec<_>>();
+        neighbors.iter_mut().enumerate().for_each(|(i, list)| {
+            let angle = |j: &usize| (sites[*j].y - sites[i].y).atan2(sites[*j].x - sites[i].x);
+            list.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+        });
+
+        let mut visited = neighbors
+            .iter()
+            .map(|list| vec![false; list.len()])
+            .collect::<Vec<_>>();
+        let mut faces = Vec::new();
+        for start in 0..neighbors.len() {
+            for k in 0..neighbors[start].len() {
+                let mut face = Vec::new();
+                let (mut node, mut k) = (start, k);
+                // turn to the next road clockwise at each node, keeping the face on the left
+                while !visited[node][k] {
+                    visited[node][k] = true;
+                    face.push(node);
+                    let next = neighbors[node][k];
+                    let list = &neighbors[next];
+                    let back = list.iter().position(|&i| i == node).unwrap_or(0);
+                    (node, k) = (next, (back + list.len() - 1) % list.len());
+                }
+                let ring = face
+                    .iter()
+                    .map(|&i| (sites[i].x, sites[i].y))
+                    .collect::<Vec<_>>();
+                if face.len() > 2 && signed_area(&ring) > 0.0 {
+                    faces.push(face);
+                }
+            }
+        }
+        faces
+    }
+
+    /// Label of the connected component of each node.
+    pub fn components(&self) -> Vec<usize> {
+        let mut labels = vec![usize::MAX; self.nodes.len()];
+        for root in 0..self.nodes.len() {
+            if labels[root] != usize::MAX {
+                continue;
+            }
+            labels[root] = root;
+            let mut stack = vec![root];
+            while let Some(node) = stack.pop() {
+                for &neighbor in &self.neighbors[node] {
+                    if labels[neighbor] == usize::MAX {
+                        labels[neighbor] = root;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+        labels
+    }
+
+    /// Connect two nodes with a stage-0 road through `via`, flagging the paths crossing the rivers.
+    ///
+    /// Where the road crosses an existing path, both are split at a node at the crossing,
+    /// so the network stays planar.
+    pub fn connect(&mut self, from: usize, to: usize, via: &[Site], river_network: &RiverNetwork) {
+        let first = self.nodes.len();
+        self.nodes.extend(
+            via.iter()
+                .map(|&site| TransportNode::new(site, Stage::new(0))),
+        );
+        self.neighbors.resize(self.nodes.len(), Vec::new());
+        let route = std::iter::once(from)
+            .chain(first..self.nodes.len())
+            .chain(std::iter::once(to))
+            .collect::<Vec<_>>();
+
+        for pair in route.windows(2) {
+            let mut current = pair[0];
+            for crossing in self.split_crossings(pair[0], pair[1], river_network) {
+                // a crossing through a node is found once for each path at the node
+                if crossing != current {
+                    self.add_path(current, crossing, river_network);
+                    current = crossing;
+                }
+            }
+            self.add_path(current, pair[1], river_network);
+        }
+    }
+
+    /// Split the paths crossing the segment from `start` to `end` at the crossings,
+    /// returning the nodes at the crossings in the order from `start`.
+    fn split_crossings(
+        &mut self,
+        start: usize,
+        end: usize,
+        river_network: &RiverNetwork,
+    ) -> Vec<usize> {
+        let (start_site, end_site) = (self.nodes[start].site, self.nodes[end].site);
+        let segment = LineSegment::new(start_site, end_site);
+        let mut crossings = self
+            .paths
+            .iter()
+            .enumerate()
+            .filter(|(_, path)| {
+                ![path.start, path.end]
+                    .iter()
+                    .any(|&i| i == start || i == end)
+            })
+            .filter_map(|(i, path)| {
+                let other =
+                    LineSegment::new(self.nodes[path.start].site, self.nodes[path.end].site);
+                let site = segment.get_intersection(&other)?;
+                // touching at the ends of the segment is not a crossing
+                (site.distance(&start_site) > CROSSING_EPSILON
+                    && site.distance(&end_site) > CROSSING_EPSILON)
+                    .then_some((start_site.distance(&site), i, site))
+            })
+            .collect::<Vec<_>>();
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        crossings
+            .into_iter()
+            .map(|(_, i, site)| {
+                let RoadPath {
+                    start: path_start,
+                    end: path_end,
+                    ..
+                } = self.paths[i];
+                // a crossing at an end of the path is the node there
+                if let Some(&node) = [path_start, path_end]
+                    .iter()
+                    .find(|&&node| self.nodes[node].site.distance(&site) <= CROSSING_EPSILON)
+                {
+                    return node;
+                }
+
+                let node = self.nodes.len();
+                self.nodes.push(TransportNode::new(site, Stage::new(0)));
+                self.neighbors.push(Vec::new());
+                for (from, to) in [(path_start, path_end), (path_end, path_start)] {
+                    if let Some(neighbor) = self.neighbors[from].iter_mut().find(|j| **j == to) {
+                        *neighbor = node;
+                    }
+                }
+                self.neighbors[node].extend([path_start, path_end]);
+                let bridge = |a: Site, b: Site| river_network.crossing_order(a, b).is_some();
+                self.paths[i] = RoadPath {
+                    start: path_start,
+                    end: node,
+                    is_bridge: bridge(self.nodes[path_start].site, site),
+                };
+                self.paths.push(RoadPath {
+                    start: path_end.min(node),
+                    end: path_end.max(node),
+                    is_bridge: bridge(site, self.nodes[path_end].site),
+                });
+                node
+            })
+            .collect()
+    }
+
+    fn add_path(&mut self, a: usize, b: usize, river_network: &RiverNetwork) {
+        let (start, end) = (a.min(b), a.max(b));
+        self.neighbors[start].push(end);
+        self.neighbors[end].push(start);
+        self.paths.push(RoadPath {
+            start,
+            end,
+            is_bridge: river_network
+                .crossing_order(self.nodes[start].site, self.nodes[end].site)
+                .is_some(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_faces() {
+        // a square split by a diagonal, with a dead end, and a separate triangle
+        let sites = [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 1.0),
+            (0.0, 1.0),
+            (2.0, 2.0),
+            (5.0, 0.0),
+            (6.0, 0.0),
+            (5.0, 1.0),
+        ];
+        let nodes = sites
+            .iter()
+            .map(|&(x, y)| TransportNode::new(Site::new(x, y), Stage::new(1)))
+            .collect();
+        let path = |start, end| RoadPath {
+            start,
+            end,
+            is_bridge: false,
+        };
+        let network = RoadNetwork::new(
+            nodes,
+            vec![
+                path(0, 1),
+                path(1, 2),
+                path(2, 3),
+                path(0, 3),
+                path(0, 2),
+                path(2, 4),
+                path(5, 6),
+                path(6, 7),
+                path(5, 7),
+            ],
+        );
+
+        let mut faces = network
+            .faces()
+            .into_iter()
+            .map(|mut face| {
+                // start from the smallest node to compare
+                let first = (0..face.len()).min_by_key(|&i| face[i]).unwrap();
+                face.rotate_left(first);
+                face
+            })
+            .collect::<Vec<_>>();
+        faces.sort();
+        assert_eq!(faces, vec![vec![0, 1, 2], vec![0, 2, 3], vec![5, 6, 7]]);
+    }
+
+    #[test]
+    fn test_connect_across() {
+        // a street from (0, -1) to (0, 1), crossed by a highway from (-1, 0) to (1, 0)
+        let sites = [
+            (0.0, -1.0),
+            (0.0, 1.0),
+            (-1.0, 0.0),
+            (1.0, 0.0),
+            (-1.0, -1.0),
+            (1.0, 1.0),
+        ];
+        let nodes = sites
+            .iter()
+            .map(|&(x, y)| TransportNode::new(Site::new(x, y), Stage::new(1)))
+            .collect();
+        let street = RoadPath {
+            start: 0,
+            end: 1,
+            is_bridge: false,
+        };
+        let river_network = RiverNetwork::new(Vec::new(), Vec::new());
+        let mut network = RoadNetwork::new(nodes, vec![street]);
+        network.connect(2, 3, &[], &river_network);
+
+        assert_eq!(network.nodes().len(), 7);
+        assert!(network.nodes()[6].site.distance(&Site::new(0.0, 0.0)) < 1e-9);
+        let mut neighbors = network.neighbors(6).to_vec();
+        neighbors.sort();
+        assert_eq!(neighbors, vec![0, 1, 2, 3]);
+        assert_eq!(network.paths().len(), 4);
+        assert!(network.paths().iter().all(|path| path.start < path.end));
+
+        // another highway through the crossing shares the node
+        network.connect(4, 5, &[], &river_network);
+        assert_eq!(network.nodes().len(), 7);
+        assert_eq!(network.neighbors(6).len(), 6);
+        assert_eq!(network.paths().len(), 6);
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use fastlem::models::surface::sites::Site2D;
+use terrain_graph::edge_attributed_undirected::EdgeAttributedUndirectedGraph;
+
+/// Cost of the route over the terrain graph.
+#[derive(Debug, Clone, Copy)]
+pub struct RouteCost {
+    /// Sites below the sea level cannot be passed.
+    pub sea_level: f64,
+    /// Climbing a unit of elevation costs as much as moving this distance.
+    pub slope_penalty: f64,
+    /// Edges steeper than this (elevation per distance) cannot be passed.
+    pub max_gradient: f64,
+}
+
+impl RouteCost {
+    /// Cost of the edge from `from` to `to`, or `None` if it cannot be passed.
+    fn edge(&self, elevations: &[f64], from: usize, to: usize, distance: f64) -> Option<f64> {
+        if elevations[to] < self.sea_level {
+            return None;
+        }
+        let climb = (elevations[to] - elevations[from]).abs();
+        if climb > distance * self.max_gradient {
+            return None;
+        }
+        Some(distance + climb * self.slope_penalty)
+    }
+}
+
+/// Entry of the open set, ordered so that `BinaryHeap` pops the smallest estimate first.
+struct Open {
+    estimate: f64,
+    index: usize,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+/// Index of the terrain site nearest to `site`.
+pub fn nearest_site(sites: &[Site2D], site: Site2D) -> Option<usize> {
+    sites
+        .iter()
+        .map(|other| (other.x - site.x).powi(2) + (other.y - site.y).powi(2))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+/// Sites reachable from `start` over the terrain graph.
+pub fn reachable(
+    elevations: &[f64],
+    graph: &EdgeAttributedUndirectedGraph<f64>,
+    start: usize,
+    cost: RouteCost,
+) -> Vec<bool> {
+    let mut reached = vec![false; elevations.len()];
+    reached[start] = true;
+    let mut stack = vec![start];
+    while let Some(index) = stack.pop() {
+        for &(neighbor, distance) in graph.neighbors_of(index) {
+            if !reached[neighbor] && cost.edge(elevations, index, neighbor, distance).is_some() {
+                reached[neighbor] = true;
+                stack.push(neighbor);
+            }
+        }
+    }
+    reached
+}
+
+/// Find the cheapest route from `start` to `goal` over the terrain graph by A*.
+///
+/// Returns the indices of the sites on the route including both ends,
+/// or `None` if the sea or steep slopes separate them. The goal itself may be below the sea level.
+pub fn find_route(
+    sites: &[Site2D],
+    elevations: &[f64],
+    graph: &EdgeAttributedUndirectedGraph<f64>,
+    start: usize,
+    goal: usize,
+    cost: RouteCost,
+) -> Option<Vec<usize>> {
+    let heuristic = |index: usize| {
+        let (site, goal) = (sites[index], sites[goal]);
+        ((site.x - goal.x).powi(2) + (site.y - goal.y).powi(2)).sqrt()
+    };
+
+    let mut costs = HashMap::from([(start, 0.0)]);
+    let mut previous = HashMap::new();
+    let mut open = BinaryHeap::from([Open {
+        estimate: heuristic(start),
+        index: start,
+    }]);
+
+    while let Some(Open { estimate, index }) = open.pop() {
+        if index == goal {
+            let mut route = vec![goal];
+            while let Some(&index) = previous.get(route.last()?) {
+                route.push(index);
+            }
+            route.reverse();
+            return Some(route);
+        }
+        let current = costs[&index];
+        if estimate > current + heuristic(index) {
+            // already reached with a lower cost
+            continue;
+        }
+
+        for &(neighbor, distance) in graph.neighbors_of(index) {
+            let edge = if neighbor == goal {
+                // the goal may be a site on the coast, so it is entered regardless of its elevation
+                Some(
+                    distance
+                        + (elevations[neighbor] - elevations[index]).abs() * cost.slope_penalty,
+                )
+            } else {
+                cost.edge(elevations, index, neighbor, distance)
+            };
+            let Some(edge) = edge else {
+                continue;
+            };
+            let next = current + edge;
+            if costs.get(&neighbor).is_none_or(|&known| next < known) {
+                costs.insert(neighbor, next);
+                previous.insert(neighbor, index);
+                open.push(Open {
+                    estimate: next + heuristic(neighbor),
+                    index: neighbor,
+                });
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_route() {
+        // a row of sites where the middle one is in the sea, with a detour over a hill
+        let sites = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (1.0, 1.0)].map(|(x, y)| Site2D { x, y });
+        let elevations = [1.0, -1.0, 1.0, 3.0];
+        let mut graph = EdgeAttributedUndirectedGraph::new(sites.len());
+        graph.add_edge(0, 1, 1.0);
+        graph.add_edge(1, 2, 1.0);
+        graph.add_edge(0, 3, 2f64.sqrt());
+        graph.add_edge(3, 2, 2f64.sqrt());
+
+        let cost = RouteCost {
+            sea_level: 0.0,
+            slope_penalty: 1.0,
+            max_gradient: f64::INFINITY,
+        };
+        assert_eq!(
+            find_route(&sites, &elevations, &graph, 0, 2, cost),
+            Some(vec![0, 3, 2])
+        );
+        assert_eq!(
+            find_route(&sites, &elevations, &graph, 0, 1, cost),
+            Some(vec![0, 1])
+        );
+
+        let gentle = RouteCost {
+            max_gradient: 1.0,
+            ..cost
+        };
+        assert_eq!(
+            reachable(&elevations, &graph, 0, gentle),
+            vec![true, false, false, false]
+        );
+
+        graph.delete_edge(3, 2);
+        assert_eq!(find_route(&sites, &elevations, &graph, 0, 2, cost), None);
+        assert_eq!(nearest_site(&sites, Site2D { x: 0.9, y: 0.8 }), Some(3));
+    }
+}
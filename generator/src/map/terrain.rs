@@ -5,8 +5,16 @@ use fastlem::{
     },
 };
 use noise::{NoiseFn, Perlin};
+use rand::Rng;
 use terrain_graph::edge_attributed_undirected::EdgeAttributedUndirectedGraph;
 
+use crate::{
+    error::GenerationError,
+    progress::{GenerationPhase, Progress},
+};
+
+use super::random::RandomStream;
+
 #[derive(Debug, Clone)]
 pub struct TerrainConfig {
     pub x_bound: f64,
@@ -60,15 +68,24 @@ pub struct TerrainBuilder {
 }
 
 impl TerrainBuilder {
-    pub fn new(config: TerrainConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let model = TerrainModel2DBulider::from_random_sites(
-            config.particle_num,
-            config.bound_min(),
-            config.bound_max(),
-        )
-        .relaxate_sites(1)?
-        .add_edge_sites(None, None)?
-        .build()?;
+    pub fn new(config: TerrainConfig, progress: &Progress) -> Result<Self, GenerationError> {
+        progress.report(GenerationPhase::SiteRelaxation, Some(0.0))?;
+        let (bound_min, bound_max) = (config.bound_min(), config.bound_max());
+        let mut rng = RandomStream::Sites.rng(config.seed);
+        let sites = (0..config.particle_num)
+            .map(|_| Site2D {
+                x: rng.gen_range(bound_min.x..bound_max.x),
+                y: rng.gen_range(bound_min.y..bound_max.y),
+            })
+            .collect();
+
+        let model = TerrainModel2DBulider::default()
+            .set_sites(sites)
+            .set_bounding_box(Some(bound_min), Some(bound_max))
+            .relaxate_sites(1)?
+            .add_edge_sites(None, None)?
+            .build()?;
+        progress.report(GenerationPhase::SiteRelaxation, Some(1.0))?;
 
         Ok(Self { config, model })
     }
@@ -77,7 +94,9 @@ impl TerrainBuilder {
         &self.model
     }
 
-    pub fn build(self) -> Result<Terrain2D, Box<dyn std::error::Error>> {
+    pub fn build(self, progress: &Progress) -> Result<Terrain2D, GenerationError> {
+        progress.report(GenerationPhase::OutletDetermination, Some(0.0))?;
+
         // Seed of the noise generator.
         // You can generate various terrains by changing the seed.
         let seed = self.config.seed;
@@ -202,7 +221,8 @@ impl TerrainBuilder {
             graph,
             self.config.convex_hull_is_always_outlet,
         )
-        .ok_or("No outlet found")?;
+        .ok_or(GenerationError::NoOutlet)?;
+        progress.report(GenerationPhase::OutletDetermination, Some(1.0))?;
 
         let erodibility_distribution_power = self.config.erodibility_distribution_power;
         let parameters = {
@@ -234,10 +254,12 @@ impl TerrainBuilder {
                 .collect::<Vec<TopographicalParameters>>()
         };
 
+        progress.report(GenerationPhase::Erosion, Some(0.0))?;
         let terrain = fastlem::lem::generator::TerrainGenerator::default()
             .set_model(self.model)
             .set_parameters(parameters)
             .generate()?;
+        progress.report(GenerationPhase::Erosion, Some(1.0))?;
 
         Ok(terrain)
     }
@@ -267,7 +289,7 @@ fn octaved_perlin(
 }
 
 fn determine_outlets(
-    sites: &Vec<Site2D>,
+    sites: &[Site2D],
     base_is_outlet: Vec<bool>,
     start_index: Vec<usize>,
     graph: &EdgeAttributedUndirectedGraph<f64>,
@@ -0,0 +1,440 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::error::GenerationError;
+
+/// Parameters of [`StandardMap`](crate::standard_map::StandardMap) generation.
+///
+/// The defaults reproduce the maps of the website.
+/// Parameters with `_min` / `_max` are sampled from the range for each seed.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationParams {
+    // terrain
+    /// Height of the map. The width is `bound * x_expand_prop`.
+    pub bound: f64,
+    pub particle_num: usize,
+    pub fault_scale: f64,
+    pub land_ratio_min: f64,
+    pub land_ratio_max: f64,
+    pub erodibility_distribution_power_min: f64,
+    pub erodibility_distribution_power_max: f64,
+    pub convex_hull_is_always_outlet: bool,
+    pub global_max_slope: Option<f64>,
+
+    // map
+    pub sea_level: f64,
+    pub max_slope_livable: f64,
+    pub origin_sample_num: usize,
+    pub max_retries: usize,
+    pub origin_min_elevation: f64,
+    /// The size of the city is `city_size_prop_min + (city_size_prop_max - city_size_prop_min) * r^city_size_power`
+    /// (multiplied by the land ratio), where `r` is uniform in `[0, 1)`.
+    pub city_size_prop_min: f64,
+    pub city_size_prop_max: f64,
+    pub city_size_power: i32,
+    pub river_drainage_area_min: f64,
+    pub path_normal_length: f64,
+    /// Place the origin at (`origin_x`, `origin_y`) instead of sampling it from the seed.
+    /// Both or neither must be set.
+    pub origin_x: Option<f64>,
+    pub origin_y: Option<f64>,
+    /// Angle (in radians) of the first road from the origin, instead of a random one.
+    pub initial_angle: Option<f64>,
+    /// Number of satellite villages besides the main town.
+    /// Fewer are placed if the land has no room for them.
+    pub satellite_num: usize,
+    /// The size of each satellite relative to the main town is sampled from the range.
+    pub satellite_size_ratio_min: f64,
+    pub satellite_size_ratio_max: f64,
+    /// Population density of the satellites relative to the main town.
+    pub satellite_weight: f64,
+    pub settlement_distance_min: f64,
+    /// Climbing a unit of elevation costs highways between settlements as much as moving this distance.
+    pub highway_slope_penalty: f64,
+
+    // railway
+    /// Whether to lay a railway across the land through the settlements.
+    pub railway: bool,
+    /// Steepest slope of the railway, in radians.
+    pub railway_max_slope: f64,
+    /// Climbing a unit of elevation costs the railway as much as moving this distance.
+    pub railway_slope_penalty: f64,
+    /// Minimum distance between stations along the railway.
+    pub station_distance_min: f64,
+    /// Stations are placed only where the population density is at least this.
+    pub station_density_min: f64,
+
+    // buildings
+    /// Whether to place buildings in the blocks enclosed by the roads.
+    pub buildings: bool,
+    /// Blocks with a lower population density have no buildings.
+    pub building_density_min: f64,
+    /// Width of the lots along the roads, each of which has a building.
+    pub building_lot_width: f64,
+    /// Distance between the roads and the buildings.
+    pub building_setback: f64,
+    /// Buildings have up to this many floors where the population density is 1.
+    pub building_floors_max: u32,
+    /// Height of a floor in the units of the map.
+    pub building_floor_height: f64,
+
+    // points of interest
+    /// A school for every this many people, and a post office and a shrine likewise.
+    pub school_population: usize,
+    pub post_office_population: usize,
+    pub shrine_population: usize,
+    /// Schools, post offices and shrines are placed only where the population density is at least this.
+    pub poi_density_min: f64,
+    /// Minimum distance between the points of interest of the same kind.
+    pub poi_distance_min: f64,
+    pub fishing_port_num_max: usize,
+    /// Fishing ports are built on the coast where at least this share of the surroundings is land.
+    pub fishing_port_shelter_min: f64,
+    pub fishing_port_distance_min: f64,
+    /// Number of 道の駅 on the main roads.
+    pub road_station_num: usize,
+    /// 道の駅 are at least this far from the settlements and from each other.
+    pub road_station_distance_min: f64,
+
+    // naming
+    /// Target length of the city name is `city_name_length - city_size_prop * city_name_length_decay`.
+    pub city_name_length: f64,
+    pub city_name_length_decay: f64,
+    pub county_name_length: f64,
+    pub subprefecture_name_length: f64,
+    /// Target length of the names of the stations away from the settlements.
+    pub station_name_length: f64,
+    pub district_name_length: f64,
+    pub street_name_length: f64,
+    /// Streets shorter than this are not named.
+    pub street_length_min: f64,
+    /// Main roads longer than this (and the longest one) are national roads (国道),
+    /// and the others are prefectural roads (道道).
+    pub national_road_length_min: f64,
+    /// Local roads are town streets (〜通) where the population density is at least this,
+    /// and rural roads (〜線) elsewhere.
+    pub avenue_density_min: f64,
+    /// Minimum distance between the centers of the districts.
+    pub district_size: f64,
+    /// Size of the blocks numbered by 条 and 丁目.
+    pub block_size: f64,
+    /// Places farther than this from the roads have no address.
+    pub address_road_distance_max: f64,
+    pub poi_name_length: f64,
+    pub name_cmp_samples: usize,
+    /// Governments with a smaller population are villages (村).
+    pub village_population_max: usize,
+    /// Governments with a smaller population are towns (町), and the others are cities (市).
+    pub town_population_max: usize,
+}
+
+impl Default for GenerationParams {
+    fn default() -> Self {
+        Self {
+            bound: 250.0,
+            particle_num: 50000,
+            fault_scale: 0.15,
+            land_ratio_min: 0.5,
+            land_ratio_max: 0.9,
+            erodibility_distribution_power_min: 3.0,
+            erodibility_distribution_power_max: 5.0,
+            convex_hull_is_always_outlet: false,
+            global_max_slope: None,
+
+            sea_level: 1e-3,
+            max_slope_livable: std::f64::consts::PI / 4.0,
+            origin_sample_num: 10,
+            max_retries: 500,
+            origin_min_elevation: 2.0,
+            city_size_prop_min: 0.01,
+            city_size_prop_max: 0.12,
+            city_size_power: 4,
+            river_drainage_area_min: 300.0,
+            path_normal_length: 0.5,
+            origin_x: None,
+            origin_y: None,
+            initial_angle: None,
+            satellite_num: 0,
+            satellite_size_ratio_min: 0.2,
+            satellite_size_ratio_max: 0.6,
+            satellite_weight: 0.5,
+            settlement_distance_min: 30.0,
+            highway_slope_penalty: 20.0,
+
+            railway: true,
+            railway_max_slope: std::f64::consts::PI / 6.0,
+            railway_slope_penalty: 100.0,
+            station_distance_min: 8.0,
+            station_density_min: 0.01,
+
+            buildings: true,
+            building_density_min: 0.01,
+            building_lot_width: 0.4,
+            building_setback: 0.05,
+            building_floors_max: 15,
+            building_floor_height: 0.03,
+            school_population: 5000,
+            post_office_population: 4000,
+            shrine_population: 8000,
+            poi_density_min: 0.01,
+            poi_distance_min: 3.0,
+            fishing_port_num_max: 2,
+            fishing_port_shelter_min: 0.5,
+            fishing_port_distance_min: 10.0,
+            road_station_num: 1,
+            road_station_distance_min: 15.0,
+            city_name_length: 3.1,
+            city_name_length_decay: 20.0,
+            county_name_length: 2.1,
+            subprefecture_name_length: 2.1,
+            station_name_length: 2.1,
+            district_name_length: 2.1,
+            street_name_length: 2.1,
+            street_length_min: 3.0,
+            national_road_length_min: 30.0,
+            avenue_density_min: 0.02,
+            district_size: 8.0,
+            block_size: 1.0,
+            address_road_distance_max: 2.0,
+            poi_name_length: 2.1,
+            name_cmp_samples: 5,
+            village_population_max: 3000,
+            town_population_max: 20000,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl GenerationParams {
+    /// Parameters with the default values.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GenerationParams {
+        Self::default()
+    }
+
+    /// Parse parameters from JSON. Missing fields take the default values.
+    pub fn from_json(json: &str) -> Result<GenerationParams, GenerationError> {
+        serde_json::from_str(json).map_err(|err| GenerationError::Params(err.to_string()))
+    }
+
+    pub fn to_json(&self) -> String {
+        // serializing plain numbers and booleans cannot fail
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Check that the values can generate a map, naming the first invalid one.
+    ///
+    /// Called by [`StandardMap::with_params`](crate::standard_map::StandardMap::with_params),
+    /// so values out of range fail there instead of panicking during the generation.
+    /// Every number is checked to be finite, and the ones used as lengths, counts, divisors
+    /// or exponents are checked to be in their ranges.
+    pub fn validate(&self) -> Result<(), GenerationError> {
+        let check = |name: &str, value: f64, valid: bool, expected: &str| {
+            if value.is_finite() && valid {
+                Ok(())
+            } else {
+                Err(GenerationError::Params(format!(
+                    "`{}` must be {}, but is {}",
+                    name, expected, value
+                )))
+            }
+        };
+        let finite = |name: &str, value: f64| check(name, value, true, "finite");
+        let positive = |name: &str, value: f64| check(name, value, value > 0.0, "positive");
+        let non_negative =
+            |name: &str, value: f64| check(name, value, value >= 0.0, "non-negative");
+        let unit =
+            |name: &str, value: f64| check(name, value, (0.0..=1.0).contains(&value), "in [0, 1]");
+        let range = |name: &str, min: f64, max: f64| {
+            if min <= max {
+                Ok(())
+            } else {
+                Err(GenerationError::Params(format!(
+                    "`{0}_min` ({1}) must not be greater than `{0}_max` ({2})",
+                    name, min, max
+                )))
+            }
+        };
+
+        // terrain
+        positive("bound", self.bound)?;
+        positive("particle_num", self.particle_num as f64)?;
+        non_negative("fault_scale", self.fault_scale)?;
+        positive("land_ratio_min", self.land_ratio_min)?;
+        unit("land_ratio_max", self.land_ratio_max)?;
+        range("land_ratio", self.land_ratio_min, self.land_ratio_max)?;
+        positive(
+            "erodibility_distribution_power_min",
+            self.erodibility_distribution_power_min,
+        )?;
+        finite(
+            "erodibility_distribution_power_max",
+            self.erodibility_distribution_power_max,
+        )?;
+        range(
+            "erodibility_distribution_power",
+            self.erodibility_distribution_power_min,
+            self.erodibility_distribution_power_max,
+        )?;
+        if let Some(global_max_slope) = self.global_max_slope {
+            positive("global_max_slope", global_max_slope)?;
+        }
+
+        // map
+        finite("sea_level", self.sea_level)?;
+        positive("max_slope_livable", self.max_slope_livable)?;
+        positive("origin_sample_num", self.origin_sample_num as f64)?;
+        finite("origin_min_elevation", self.origin_min_elevation)?;
+        positive("city_size_prop_min", self.city_size_prop_min)?;
+        finite("city_size_prop_max", self.city_size_prop_max)?;
+        range(
+            "city_size_prop",
+            self.city_size_prop_min,
+            self.city_size_prop_max,
+        )?;
+        non_negative("city_size_power", self.city_size_power as f64)?;
+        non_negative("river_drainage_area_min", self.river_drainage_area_min)?;
+        positive("path_normal_length", self.path_normal_length)?;
+        if self.origin_x.is_some() != self.origin_y.is_some() {
+            return Err(GenerationError::Params(
+                "`origin_x` and `origin_y` must be set together".to_string(),
+            ));
+        }
+        if let Some((x, y)) = self.origin_x.zip(self.origin_y) {
+            finite("origin_x", x)?;
+            finite("origin_y", y)?;
+        }
+        if let Some(initial_angle) = self.initial_angle {
+            finite("initial_angle", initial_angle)?;
+        }
+        if self.satellite_num > 0 {
+            positive("satellite_size_ratio_min", self.satellite_size_ratio_min)?;
+            finite("satellite_size_ratio_max", self.satellite_size_ratio_max)?;
+            range(
+                "satellite_size_ratio",
+                self.satellite_size_ratio_min,
+                self.satellite_size_ratio_max,
+            )?;
+            positive("satellite_weight", self.satellite_weight)?;
+        }
+        positive("settlement_distance_min", self.settlement_distance_min)?;
+        non_negative("highway_slope_penalty", self.highway_slope_penalty)?;
+
+        // railway
+        positive("railway_max_slope", self.railway_max_slope)?;
+        non_negative("railway_slope_penalty", self.railway_slope_penalty)?;
+        positive("station_distance_min", self.station_distance_min)?;
+        finite("station_density_min", self.station_density_min)?;
+
+        // buildings
+        finite("building_density_min", self.building_density_min)?;
+        positive("building_lot_width", self.building_lot_width)?;
+        non_negative("building_setback", self.building_setback)?;
+        positive("building_floors_max", self.building_floors_max as f64)?;
+        positive("building_floor_height", self.building_floor_height)?;
+
+        // points of interest
+        positive("school_population", self.school_population as f64)?;
+        positive("post_office_population", self.post_office_population as f64)?;
+        positive("shrine_population", self.shrine_population as f64)?;
+        finite("poi_density_min", self.poi_density_min)?;
+        positive("poi_distance_min", self.poi_distance_min)?;
+        unit("fishing_port_shelter_min", self.fishing_port_shelter_min)?;
+        positive("fishing_port_distance_min", self.fishing_port_distance_min)?;
+        positive("road_station_distance_min", self.road_station_distance_min)?;
+
+        // naming
+        finite("city_name_length", self.city_name_length)?;
+        finite("city_name_length_decay", self.city_name_length_decay)?;
+        finite("county_name_length", self.county_name_length)?;
+        finite("subprefecture_name_length", self.subprefecture_name_length)?;
+        finite("station_name_length", self.station_name_length)?;
+        finite("district_name_length", self.district_name_length)?;
+        finite("street_name_length", self.street_name_length)?;
+        non_negative("street_length_min", self.street_length_min)?;
+        non_negative("national_road_length_min", self.national_road_length_min)?;
+        finite("avenue_density_min", self.avenue_density_min)?;
+        positive("district_size", self.district_size)?;
+        positive("block_size", self.block_size)?;
+        positive("address_road_distance_max", self.address_road_distance_max)?;
+        finite("poi_name_length", self.poi_name_length)?;
+        positive("name_cmp_samples", self.name_cmp_samples as f64)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_json() {
+        let params = GenerationParams::from_json(r#"{ "particle_num": 1000 }"#).unwrap();
+        assert_eq!(params.particle_num, 1000);
+        assert_eq!(params.bound, GenerationParams::default().bound);
+
+        let params = GenerationParams::from_json(&params.to_json()).unwrap();
+        assert_eq!(params.particle_num, 1000);
+
+        assert!(GenerationParams::from_json(r#"{ "particle_num": -1 }"#).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(GenerationParams::default().validate().is_ok());
+        let invalid = [
+            GenerationParams {
+                bound: 0.0,
+                ..Default::default()
+            },
+            GenerationParams {
+                particle_num: 0,
+                ..Default::default()
+            },
+            GenerationParams {
+                city_size_prop_min: 0.0,
+                city_size_prop_max: 0.0,
+                ..Default::default()
+            },
+            GenerationParams {
+                land_ratio_min: 0.9,
+                land_ratio_max: 0.5,
+                ..Default::default()
+            },
+            GenerationParams {
+                origin_x: Some(0.0),
+                ..Default::default()
+            },
+            GenerationParams {
+                path_normal_length: 0.0,
+                ..Default::default()
+            },
+            GenerationParams {
+                city_size_power: -1,
+                ..Default::default()
+            },
+            GenerationParams {
+                fishing_port_shelter_min: 1.5,
+                ..Default::default()
+            },
+            GenerationParams {
+                initial_angle: Some(f64::NAN),
+                ..Default::default()
+            },
+            GenerationParams {
+                school_population: 0,
+                ..Default::default()
+            },
+            GenerationParams {
+                satellite_num: 2,
+                satellite_size_ratio_min: 0.0,
+                ..Default::default()
+            },
+        ];
+        assert!(invalid
+            .iter()
+            .all(|params| matches!(params.validate(), Err(GenerationError::Params(_)))));
+    }
+}
@@ -1,6 +1,8 @@
 use name_engine::{Name, NameGeneratorBuilder, SyllableInfo};
 use rand::{rngs::StdRng, Rng, SeedableRng};
 
+use crate::error::GenerationError;
+
 pub struct NameConfig {
     pub target_name_length: f64,
     pub cmp_samples: usize,
@@ -19,20 +21,16 @@ impl NameGenerator {
                 if line.is_empty() {
                     return None;
                 }
-                let split = line.split(',');
+                let mut split = line.split(',');
                 let phrases = split
-                    .last()?
+                    .next_back()?
                     .split(':')
                     .filter_map(|phrase| {
                         let mut split = phrase.split('_');
                         Some((split.next()?, split.next()?))
                     })
                     .collect::<Vec<(&str, &str)>>();
-                if let Ok(name) = Name::new(phrases) {
-                    Some(name)
-                } else {
-                    None
-                }
+                Name::new(phrases).ok()
             })
             .collect::<Vec<Name>>();
 
@@ -78,7 +76,7 @@ impl NameGenerator {
         Some(-(target_name_length - name.chars().count() as f64).abs())
     }
 
-    pub fn generate(&mut self, config: NameConfig) -> Option<(String, String)> {
+    pub fn generate(&mut self, config: NameConfig) -> Result<(String, String), GenerationError> {
         (0..config.cmp_samples)
             .filter_map(|_| {
                 let (name, pronunciation, syllable_info) =
@@ -88,6 +86,7 @@ impl NameGenerator {
             })
             .max_by(|(_, _, score1), (_, _, score2)| score1.total_cmp(score2))
             .map(|(name, pronunciation, _)| (name, pronunciation))
+            .ok_or(GenerationError::NameGeneration)
     }
 }
 
@@ -100,7 +99,7 @@ mod tests {
         let csv_file = include_str!("../../frontend/static/dataset/placenames.csv");
         let mut generator = NameGenerator::new(csv_file, 0);
         (0..1000).for_each(|_| {
-            if let Some((name, pronunciation)) = generator.generate(NameConfig {
+            if let Ok((name, pronunciation)) = generator.generate(NameConfig {
                 target_name_length: 3.0,
                 cmp_samples: 5,
             }) {
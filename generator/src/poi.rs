@@ -0,0 +1,350 @@
+use std::{collections::HashSet, f64::consts::PI};
+
+use fastlem::models::surface::sites::Site2D;
+use rand::{seq::SliceRandom, Rng};
+use street_engine::core::geometry::site::Site;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    map::{random::RandomStream, Map},
+    params::GenerationParams,
+    placename::{NameConfig, NameGenerator},
+    types::{MapSite, Name, NameSet},
+};
+
+/// Kind of a point of interest.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PoiKind {
+    /// 市役所, 町役場 or 村役場 of the city.
+    TownHall = 0,
+    /// 小学校 or 中学校.
+    School = 1,
+    /// 郵便局.
+    PostOffice = 2,
+    /// 神社 on a hill.
+    Shrine = 3,
+    /// 漁港 on a sheltered coast.
+    FishingPort = 4,
+    /// 道の駅, a rest area on a main road.
+    RoadStation = 5,
+}
+
+impl PoiKind {
+    pub(crate) fn from_code(code: u8) -> Option<Self> {
+        [
+            PoiKind::TownHall,
+            PoiKind::School,
+            PoiKind::PostOffice,
+            PoiKind::Shrine,
+            PoiKind::FishingPort,
+            PoiKind::RoadStation,
+        ]
+        .into_iter()
+        .find(|kind| *kind as u8 == code)
+    }
+}
+
+/// A point of interest, such as a school or a shrine.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Poi {
+    kind: PoiKind,
+    site: Site,
+    name: Name,
+}
+
+#[wasm_bindgen]
+impl Poi {
+    pub fn kind(&self) -> PoiKind {
+        self.kind
+    }
+
+    pub fn site(&self) -> MapSite {
+        MapSite {
+            x: self.site.x,
+            y: self.site.y,
+        }
+    }
+
+    /// Full name, e.g. `〜小学校` or `道の駅〜`.
+    pub fn name(&self) -> Name {
+        self.name.clone()
+    }
+}
+
+impl Poi {
+    pub(crate) fn new(kind: PoiKind, site: Site, name: Name) -> Self {
+        Self { kind, site, name }
+    }
+}
+
+/// Distance within which the sea makes a road node coastal.
+const COAST_DISTANCE: f64 = 1.0;
+/// Road nodes higher than this above the sea are not looked at for the coast.
+const COAST_HEIGHT_MAX: f64 = 3.0;
+/// Radius of the surroundings looked at for the shelter of a fishing port.
+const SHELTER_RADIUS: f64 = 5.0;
+/// Distance from the road nodes to the hills looked at for shrines.
+const HILL_DISTANCE: f64 = 1.0;
+/// Times to generate a name again if it is already used.
+const NAME_RETRIES: usize = 5;
+
+/// Place the points of interest of the map and name them.
+///
+/// The numbers of schools, post offices and shrines grow with the population,
+/// and they are spread over the roads in the towns weighted by the population density.
+/// Points whose names fail to generate are named after the city with a number.
+pub(crate) fn generate(
+    map: &Map,
+    nameset: &NameSet,
+    namegen: &mut NameGenerator,
+    params: &GenerationParams,
+    seed: u32,
+) -> Vec<Poi> {
+    let nodes = map.network.nodes();
+    let elevation = |site: Site| {
+        map.terrain
+            .get_elevation(&Site2D {
+                x: site.x,
+                y: site.y,
+            })
+            .unwrap_or(f64::NEG_INFINITY)
+    };
+    let is_land = |site: Site| elevation(site) >= map.sea_level;
+    let elevations = nodes
+        .iter()
+        .map(|node| elevation(node.site))
+        .collect::<Vec<_>>();
+    // nodes in the towns, with their population densities
+    let towns = nodes
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| elevations[i] >= map.sea_level)
+        .map(|(i, node)| (i, map.population_density_at(node.site.x, node.site.y)))
+        .filter(|&(_, density)| density >= params.poi_density_min)
+        .collect::<Vec<_>>();
+    let mut rnd = RandomStream::Pois.rng(seed);
+
+    let mut sites: Vec<(PoiKind, Site)> = Vec::new();
+
+    if let Some(node) = nodes.iter().min_by(|a, b| {
+        a.site
+            .distance(&map.origin)
+            .total_cmp(&b.site.distance(&map.origin))
+    }) {
+        sites.push((PoiKind::TownHall, node.site));
+    }
+
+    // weighted random order of the nodes by the population density
+    let mut weighted = |count: usize| {
+        let candidates = towns
+            .iter()
+            .map(|&(i, density)| (nodes[i].site, rnd.gen::<f64>().ln() / density))
+            .collect();
+        spread(candidates, count, params.poi_distance_min)
+    };
+    let count = |population_per: usize| map.population.div_ceil(population_per.max(1)).max(1);
+    let schools = weighted(count(params.school_population));
+    let post_offices = weighted(count(params.post_office_population));
+    sites.extend(schools.into_iter().map(|site| (PoiKind::School, site)));
+    sites.extend(
+        post_offices
+            .into_iter()
+            .map(|site| (PoiKind::PostOffice, site)),
+    );
+
+    // the highest place around a road in the town, by how much it rises above the road
+    let hills = towns
+        .iter()
+        .filter_map(|&(i, density)| {
+            let (hill, height) = around(nodes[i].site, HILL_DISTANCE, 8)
+                .map(|site| (site, elevation(site)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))?;
+            let prominence = height - elevations[i];
+            (prominence > 0.0).then_some((hill, prominence * density))
+        })
+        .collect();
+    sites.extend(
+        spread(
+            hills,
+            count(params.shrine_population),
+            params.poi_distance_min,
+        )
+        .into_iter()
+        .map(|site| (PoiKind::Shrine, site)),
+    );
+
+    // a coast is sheltered if its surroundings are mostly land, as in a bay
+    let coasts = nodes
+        .iter()
+        .enumerate()
+        .filter(|&(i, node)| {
+            (map.sea_level..map.sea_level + COAST_HEIGHT_MAX).contains(&elevations[i])
+                && around(node.site, COAST_DISTANCE, 8).any(|site| !is_land(site))
+        })
+        .map(|(_, node)| node)
+        .map(|node| {
+            let land = around(node.site, SHELTER_RADIUS, 16)
+                .filter(|&site| is_land(site))
+                .count();
+            (node.site, land as f64 / 16.0)
+        })
+        .filter(|&(_, shelter)| shelter >= params.fishing_port_shelter_min)
+        .collect();
+    sites.extend(
+        spread(
+            coasts,
+            params.fishing_port_num_max,
+            params.fishing_port_distance_min,
+        )
+        .into_iter()
+        .map(|site| (PoiKind::FishingPort, site)),
+    );
+
+    let mut main_roads = nodes
+        .iter()
+        .enumerate()
+        .filter(|&(i, node)| node.stage.as_num() == 0 && elevations[i] >= map.sea_level)
+        .map(|(_, node)| node)
+        .filter(|node| {
+            map.settlements.iter().all(|settlement| {
+                settlement.site.distance(&node.site) >= params.road_station_distance_min
+            })
+        })
+        .map(|node| node.site)
+        .collect::<Vec<_>>();
+    main_roads.shuffle(&mut rnd);
+    sites.extend(
+        spread(
+            main_roads.into_iter().map(|site| (site, 0.0)).collect(),
+            params.road_station_num,
+            params.road_station_distance_min,
+        )
+        .into_iter()
+        .map(|site| (PoiKind::RoadStation, site)),
+    );
+
+    let name_config = || NameConfig {
+        target_name_length: params.poi_name_length,
+        cmp_samples: params.name_cmp_samples,
+    };
+    let mut used = HashSet::new();
+    let mut schools = 0;
+    let mut numbered = 0;
+    sites
+        .into_iter()
+        .map(|(kind, site)| {
+            let name = if kind == PoiKind::TownHall {
+                let (office, office_reading) = if nameset.government().name() == "市" {
+                    ("役所", "yakusho")
+                } else {
+                    ("役場", "yakuba")
+                };
+                (
+                    format!(
+                        "{}{}{}",
+                        nameset.city_name().name(),
+                        nameset.government().name(),
+                        office
+                    ),
+                    format!(
+                        "{} {}{}",
+                        nameset.city_name().reading(),
+                        nameset.government().reading(),
+                        office_reading
+                    ),
+                )
+            } else {
+                // a few retries to avoid the names already used, which are rare in a city
+                let mut name = namegen.generate(name_config()).ok();
+                for _ in 0..NAME_RETRIES {
+                    if name.as_ref().is_some_and(|name| !used.contains(&name.0)) {
+                        break;
+                    }
+                    name = namegen.generate(name_config()).ok();
+                }
+                let (name, reading) = name.unwrap_or_else(|| {
+                    numbered += 1;
+                    (
+                        format!("{}第{}", nameset.city_name().name(), numbered),
+                        format!("{} dai {}", nameset.city_name().reading(), numbered),
+                    )
+                });
+                used.insert(name.clone());
+                match kind {
+                    PoiKind::School => {
+                        schools += 1;
+                        // a junior high school for every two elementary schools
+                        if schools % 3 == 0 {
+                            (format!("{}中学校", name), format!("{} chugakko", reading))
+                        } else {
+                            (format!("{}小学校", name), format!("{} shogakko", reading))
+                        }
+                    }
+                    PoiKind::PostOffice => {
+                        (format!("{}郵便局", name), format!("{} yubinkyoku", reading))
+                    }
+                    PoiKind::Shrine => (format!("{}神社", name), format!("{} jinja", reading)),
+                    PoiKind::FishingPort => (format!("{}漁港", name), format!("{} gyoko", reading)),
+                    _ => (
+                        format!("道の駅{}", name),
+                        format!("michi no eki {}", reading),
+                    ),
+                }
+            };
+            Poi::new(kind, site, Name::from_tuple(name))
+        })
+        .collect()
+}
+
+/// Sites at `n` directions around `site`.
+fn around(site: Site, radius: f64, n: usize) -> impl Iterator<Item = Site> {
+    (0..n).map(move |i| {
+        let (sin, cos) = (PI * 2.0 * i as f64 / n as f64).sin_cos();
+        Site {
+            x: site.x + radius * cos,
+            y: site.y + radius * sin,
+        }
+    })
+}
+
+/// Pick up to `count` sites from the highest keys, keeping them `distance_min` apart.
+///
+/// Sites with equal keys are taken in the given order.
+fn spread(mut candidates: Vec<(Site, f64)>, count: usize, distance_min: f64) -> Vec<Site> {
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut picked: Vec<Site> = Vec::new();
+    for (site, _) in candidates {
+        if picked.len() >= count {
+            break;
+        }
+        if picked
+            .iter()
+            .all(|other| other.distance(&site) >= distance_min)
+        {
+            picked.push(site);
+        }
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spread() {
+        let candidates = [(0.0, 0.5), (1.0, 0.9), (2.5, 0.7), (5.0, 0.1), (9.0, 0.0)]
+            .map(|(x, key)| (Site::new(x, 0.0), key))
+            .to_vec();
+        let xs = |sites: Vec<Site>| sites.iter().map(|site| site.x).collect::<Vec<_>>();
+        assert_eq!(xs(spread(candidates.clone(), 3, 2.0)), vec![1.0, 5.0, 9.0]);
+        assert_eq!(xs(spread(candidates.clone(), 1, 2.0)), vec![1.0]);
+        assert_eq!(
+            xs(spread(candidates, 10, 1.0)),
+            vec![1.0, 2.5, 0.0, 5.0, 9.0]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use wasm_bindgen::prelude::*;
+
+use crate::error::GenerationError;
+
+/// Phases of map generation, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationPhase {
+    SiteRelaxation,
+    OutletDetermination,
+    Erosion,
+    PopulationDensity,
+    RiverExtraction,
+    RoadIteration,
+    Railway,
+    Naming,
+}
+
+impl GenerationPhase {
+    /// Identifier of the phase, passed to JS callbacks.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GenerationPhase::SiteRelaxation => "site_relaxation",
+            GenerationPhase::OutletDetermination => "outlet_determination",
+            GenerationPhase::Erosion => "erosion",
+            GenerationPhase::PopulationDensity => "population_density",
+            GenerationPhase::RiverExtraction => "river_extraction",
+            GenerationPhase::RoadIteration => "road_iteration",
+            GenerationPhase::Railway => "railway",
+            GenerationPhase::Naming => "naming",
+        }
+    }
+}
+
+/// Flag to cancel a running generation, shared between its clones.
+///
+/// In the browser, generation blocks the thread, so cancel the token
+/// from the progress callback (e.g. when a newer request has arrived).
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[wasm_bindgen]
+impl CancellationToken {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> CancellationToken {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+type ProgressCallback<'a> = Box<dyn Fn(GenerationPhase, Option<f64>) + 'a>;
+
+/// Receives the progress of generation and tells whether to stop.
+///
+/// The callback gets the current phase and the fraction of the phase done (`0.0..=1.0`),
+/// which is `None` when the amount of the remaining work is unknown (e.g. growing roads).
+#[derive(Default)]
+pub struct Progress<'a> {
+    callback: Option<ProgressCallback<'a>>,
+    token: CancellationToken,
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(
+        callback: impl Fn(GenerationPhase, Option<f64>) + 'a,
+        token: CancellationToken,
+    ) -> Self {
+        Self {
+            callback: Some(Box::new(callback)),
+            token,
+        }
+    }
+
+    /// Report the progress, and fail with [`GenerationError::Cancelled`] if cancelled.
+    pub(crate) fn report(
+        &self,
+        phase: GenerationPhase,
+        fraction: Option<f64>,
+    ) -> Result<(), GenerationError> {
+        if let Some(callback) = &self.callback {
+            callback(phase, fraction);
+        }
+        self.check()
+    }
+
+    /// Fail with [`GenerationError::Cancelled`] if cancelled.
+    pub(crate) fn check(&self) -> Result<(), GenerationError> {
+        if self.token.is_cancelled() {
+            Err(GenerationError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use rayon::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::buffer::ElevationBuffer;
+
+/// Piecewise linear mapping from elevations to colors.
+///
+/// `colors[i]` is placed at `weights[i]`; values outside the stops take the color of the nearest end.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Colormap {
+    colors: Vec<[u8; 3]>,
+    weights: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl Colormap {
+    /// Create a colormap from flattened RGB triples and their weights (in ascending order).
+    ///
+    /// Extra colors or weights are ignored.
+    #[wasm_bindgen(constructor)]
+    pub fn new(colors: Vec<u8>, weights: Vec<f64>) -> Colormap {
+        Self::from_stops(
+            &colors
+                .chunks_exact(3)
+                .map(|c| [c[0], c[1], c[2]])
+                .zip(weights)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// The colormap of the website in the light mode.
+    pub fn light() -> Colormap {
+        Self::from_stops(&[
+            ([170, 200, 220], 0.0),
+            ([240, 240, 210], 0.1),
+            ([215, 230, 170], 0.15),
+            ([25, 100, 25], 40.0),
+            ([15, 60, 15], 80.0),
+        ])
+    }
+
+    /// The colormap of the website in the dark mode.
+    pub fn dark() -> Colormap {
+        Self::from_stops(&[
+            ([77, 82, 114], 0.0),
+            ([210, 210, 210], 0.1),
+            ([215, 230, 170], 0.15),
+            ([25, 100, 25], 40.0),
+            ([15, 60, 15], 80.0),
+        ])
+    }
+
+    /// Grayscale colormap from black (0) to white (100).
+    pub fn heightmap() -> Colormap {
+        Self::from_stops(&[([0, 0, 0], 0.0), ([255, 255, 255], 100.0)])
+    }
+
+    /// Colormap with a blue sea, used for the images of the CLI.
+    pub fn topographic() -> Colormap {
+        Self::from_stops(&[
+            ([70, 150, 200], 0.0),
+            ([70, 150, 200], 0.05),
+            ([240, 240, 210], 0.125),
+            ([190, 200, 120], 0.5),
+            ([25, 100, 25], 25.0),
+            ([15, 60, 15], 40.0),
+        ])
+    }
+
+    /// The color of the value as `[r, g, b]`.
+    pub fn get_color(&self, value: f64) -> Vec<u8> {
+        self.color(value).to_vec()
+    }
+}
+
+impl Colormap {
+    pub fn from_stops(stops: &[([u8; 3], f64)]) -> Colormap {
+        Colormap {
+            colors: stops.iter().map(|(color, _)| *color).collect(),
+            weights: stops.iter().map(|(_, weight)| *weight).collect(),
+        }
+    }
+
+    pub fn color(&self, value: f64) -> [u8; 3] {
+        let i = self
+            .weights
+            .iter()
+            .position(|weight| value <= *weight)
+            .unwrap_or(self.weights.len());
+        if self.colors.is_empty() {
+            return [0, 0, 0];
+        }
+        if i == 0 {
+            return self.colors[0];
+        }
+        if i == self.weights.len() {
+            return self.colors[self.colors.len() - 1];
+        }
+        let (color1, color2) = (self.colors[i - 1], self.colors[i]);
+        let (weight1, weight2) = (self.weights[i - 1], self.weights[i]);
+        let ratio = (value - weight1) / (weight2 - weight1);
+        [0, 1, 2].map(|k| (color1[k] as f64 * (1.0 - ratio) + color2[k] as f64 * ratio) as u8)
+    }
+}
+
+/// How an [`ElevationBuffer`] is rendered into an image.
+///
+/// The colors are blended with `background` by `global_alpha`,
+/// and the image fades out within `fade_radius` pixels from the edges.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct RenderStyle {
+    colormap: Colormap,
+    fade_radius: f64,
+    global_alpha: f64,
+    background: [u8; 3],
+}
+
+#[wasm_bindgen]
+impl RenderStyle {
+    /// `background` is an RGB triple.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        colormap: &Colormap,
+        fade_radius: f64,
+        global_alpha: f64,
+        background: Vec<u8>,
+    ) -> RenderStyle {
+        let channel = |i: usize| background.get(i).copied().unwrap_or(0);
+        RenderStyle {
+            colormap: colormap.clone(),
+            fade_radius,
+            global_alpha,
+            background: [channel(0), channel(1), channel(2)],
+        }
+    }
+
+    /// The map of the website in the light mode.
+    pub fn light() -> RenderStyle {
+        Self::new(&Colormap::light(), 8.0, 0.65, vec![255, 255, 255])
+    }
+
+    /// The map of the website in the dark mode.
+    pub fn dark() -> RenderStyle {
+        Self::new(&Colormap::dark(), 8.0, 0.4, vec![0, 0, 0])
+    }
+
+    /// The heightmap of the website.
+    pub fn heightmap() -> RenderStyle {
+        Self::new(&Colormap::heightmap(), 0.0, 1.0, vec![255, 255, 255])
+    }
+
+    /// Opaque image with [`Colormap::topographic`].
+    pub fn topographic() -> RenderStyle {
+        Self::new(&Colormap::topographic(), 0.0, 1.0, vec![255, 255, 255])
+    }
+
+    /// Render the buffer into row-major RGBA pixels (not premultiplied),
+    /// which can be passed to `ImageData` as they are.
+    pub fn render(&self, buffer: &ElevationBuffer) -> Vec<u8> {
+        let (width, height) = (buffer.width() as usize, buffer.height() as usize);
+        let mut rgba = vec![0; width * height * 4];
+        rgba.par_chunks_mut(4)
+            .zip(buffer.as_slice().par_iter())
+            .enumerate()
+            .for_each(|(i, (pixel, &elevation))| {
+                let color = self.colormap.color(elevation);
+                (0..3).for_each(|k| {
+                    pixel[k] = (color[k] as f64 * self.global_alpha
+                        + self.background[k] as f64 * (1.0 - self.global_alpha))
+                        .round()
+                        .clamp(0.0, 255.0) as u8;
+                });
+                pixel[3] =
+                    (255.0 * self.edge_alpha(i % width, i / width, width, height)).round() as u8;
+            });
+        rgba
+    }
+}
+
+impl RenderStyle {
+    fn edge_alpha(&self, x: usize, y: usize, width: usize, height: usize) -> f64 {
+        if self.fade_radius <= 0.0 {
+            return 1.0;
+        }
+        let distance = x.min(width - x).min(y).min(height - y) as f64;
+        (distance / self.fade_radius).min(1.0).powf(0.8)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RenderStyle {
+    /// Render the buffer into a pixmap, on which other features can be drawn.
+    pub fn render_pixmap(&self, buffer: &ElevationBuffer) -> Option<tiny_skia::Pixmap> {
+        let mut pixmap = tiny_skia::Pixmap::new(buffer.width(), buffer.height())?;
+        pixmap
+            .pixels_mut()
+            .iter_mut()
+            .zip(self.render(buffer).chunks_exact(4))
+            .for_each(|(pixel, rgba)| {
+                *pixel =
+                    tiny_skia::ColorU8::from_rgba(rgba[0], rgba[1], rgba[2], rgba[3]).premultiply();
+            });
+        Some(pixmap)
+    }
+
+    /// Render the buffer and save it as a PNG file.
+    pub fn save_png<P: AsRef<std::path::Path>>(
+        &self,
+        buffer: &ElevationBuffer,
+        path: P,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.render_pixmap(buffer)
+            .ok_or("invalid image size")?
+            .save_png(path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colormap() {
+        let colormap = Colormap::new(vec![0, 0, 0, 200, 100, 50], vec![0.0, 10.0]);
+        assert_eq!(colormap.color(-1.0), [0, 0, 0]);
+        assert_eq!(colormap.color(5.0), [100, 50, 25]);
+        assert_eq!(colormap.color(20.0), [200, 100, 50]);
+    }
+}
@@ -0,0 +1,636 @@
+use std::collections::HashSet;
+
+use bincode::Options;
+use fastlem::{
+    core::traits::Model,
+    models::surface::{builder::TerrainModel2DBulider, sites::Site2D},
+};
+use naturalneighbor::Interpolator;
+use serde::{Deserialize, Serialize};
+use street_engine::{
+    core::{geometry::site::Site, Stage},
+    transport::node::TransportNode,
+};
+
+use crate::{
+    address::{Addressing, District, NamedStreet, StreetKind},
+    building::Building,
+    error::GenerationError,
+    map::{
+        railway::Railway,
+        river::{River, RiverNetwork},
+        road::{RoadNetwork, RoadPath},
+        Map, Settlement,
+    },
+    poi::{Poi, PoiKind},
+    types::{Name, NameSet},
+};
+
+/// Version of the layout of [`Snapshot`], frozen at the first release.
+///
+/// Snapshots of unreleased builds are not kept, so the layout may change without a new
+/// version until then. Increment it whenever the layout changes after the release.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything needed to restore a generated map without running the simulation again.
+///
+/// The interpolators and the spatial indices are rebuilt on restoring.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    version: u32,
+    bound_min: [f64; 2],
+    bound_max: [f64; 2],
+    sites: Vec<[f64; 2]>,
+    elevations: Vec<f64>,
+    drainage_areas: Vec<f64>,
+    population_densities: Vec<f64>,
+    rivers: Vec<RiverSnapshot>,
+    nodes: Vec<NodeSnapshot>,
+    paths: Vec<PathSnapshot>,
+    origin: [f64; 2],
+    initial_angle: f64,
+    population: usize,
+    sea_level: f64,
+    nameset: [(String, String); 5],
+    settlements: Vec<SettlementSnapshot>,
+    railway: Vec<[f64; 2]>,
+    stations: Vec<StationSnapshot>,
+    streets: Vec<StreetSnapshot>,
+    districts: Vec<DistrictSnapshot>,
+    block_size: f64,
+    address_road_distance_max: f64,
+    buildings: Vec<BuildingSnapshot>,
+    pois: Vec<PoiSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SettlementSnapshot {
+    site: [f64; 2],
+    city_size_prop: f64,
+    population: usize,
+    name: (String, String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct StationSnapshot {
+    /// Index of the site of the railway.
+    index: usize,
+    name: (String, String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct RiverSnapshot {
+    sites: Vec<[f64; 2]>,
+    order: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NodeSnapshot {
+    site: [f64; 2],
+    stage: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PathSnapshot {
+    start: usize,
+    end: usize,
+    is_bridge: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StreetSnapshot {
+    nodes: Vec<usize>,
+    kind: u8,
+    name: (String, String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct DistrictSnapshot {
+    center: [f64; 2],
+    name: (String, String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct BuildingSnapshot {
+    corners: [[f64; 2]; 4],
+    floors: u32,
+    height: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PoiSnapshot {
+    kind: u8,
+    site: [f64; 2],
+    name: (String, String),
+}
+
+/// A restored map with its bounds and names.
+pub(crate) struct Restored {
+    pub map: Map,
+    pub bound_min: Site,
+    pub bound_max: Site,
+    pub nameset: NameSet,
+    pub settlement_names: Vec<Name>,
+    pub station_names: Vec<Name>,
+    pub addressing: Addressing,
+    pub buildings: Vec<Building>,
+    pub pois: Vec<Poi>,
+}
+
+fn to_array(site: Site) -> [f64; 2] {
+    [site.x, site.y]
+}
+
+fn to_site([x, y]: [f64; 2]) -> Site {
+    Site { x, y }
+}
+
+impl Snapshot {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        map: &Map,
+        bound_min: Site,
+        bound_max: Site,
+        nameset: &NameSet,
+        settlement_names: &[Name],
+        station_names: &[Name],
+        addressing: &Addressing,
+        buildings: &[Building],
+        pois: &[Poi],
+    ) -> Self {
+        let name = |name: &Name| (name.name(), name.reading());
+        Self {
+            version: SNAPSHOT_VERSION,
+            bound_min: to_array(bound_min),
+            bound_max: to_array(bound_max),
+            sites: map
+                .terrain
+                .sites()
+                .iter()
+                .map(|site| [site.x, site.y])
+                .collect(),
+            elevations: map.terrain.elevations().to_vec(),
+            drainage_areas: map.river_network.drainage_areas.clone(),
+            population_densities: map.population_densities.clone(),
+            rivers: map
+                .river_network
+                .rivers
+                .iter()
+                .map(|river| RiverSnapshot {
+                    sites: river.sites.iter().copied().map(to_array).collect(),
+                    order: river.order,
+                })
+                .collect(),
+            nodes: map
+                .network
+                .nodes()
+                .iter()
+                .map(|node| NodeSnapshot {
+                    site: to_array(node.site),
+                    stage: node.stage.as_num(),
+                })
+                .collect(),
+            paths: map
+                .network
+                .paths()
+                .iter()
+                .map(|path| PathSnapshot {
+                    start: path.start,
+                    end: path.end,
+                    is_bridge: path.is_bridge,
+                })
+                .collect(),
+            origin: to_array(map.origin),
+            initial_angle: map.initial_angle,
+            population: map.population,
+            sea_level: map.sea_level,
+            nameset: [
+                name(&nameset.city_name()),
+                name(&nameset.county_name()),
+                name(&nameset.subprefecture_name()),
+                name(&nameset.subprefecture_postfix()),
+                name(&nameset.government()),
+            ],
+            settlements: map
+                .settlements
+                .iter()
+                .zip(settlement_names)
+                .map(|(settlement, settlement_name)| SettlementSnapshot {
+                    site: to_array(settlement.site),
+                    city_size_prop: settlement.city_size_prop,
+                    population: settlement.population,
+                    name: name(settlement_name),
+                })
+                .collect(),
+            railway: map.railway.sites.iter().copied().map(to_array).collect(),
+            stations: map
+                .railway
+                .stations
+                .iter()
+                .zip(station_names)
+                .map(|(&index, station_name)| StationSnapshot {
+                    index,
+                    name: name(station_name),
+                })
+                .collect(),
+            streets: addressing
+                .streets
+                .iter()
+                .map(|street| StreetSnapshot {
+                    nodes: street.nodes.clone(),
+                    kind: street.kind as u8,
+                    name: name(&street.name),
+                })
+                .collect(),
+            districts: addressing
+                .districts
+                .iter()
+                .map(|district| DistrictSnapshot {
+                    center: to_array(district.center),
+                    name: name(&district.name),
+                })
+                .collect(),
+            block_size: addressing.block_size,
+            address_road_distance_max: addressing.road_distance_max,
+            buildings: buildings
+                .iter()
+                .map(|building| BuildingSnapshot {
+                    corners: building.corners().map(to_array),
+                    floors: building.floors(),
+                    height: building.height(),
+                })
+                .collect(),
+            pois: pois
+                .iter()
+                .map(|poi| PoiSnapshot {
+                    kind: poi.kind() as u8,
+                    site: [poi.site().x, poi.site().y],
+                    name: name(&poi.name()),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // serializing plain numbers and strings without a size limit cannot fail
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GenerationError> {
+        // same encoding as `bincode::serialize`, limited to the given bytes
+        // so that a corrupted length cannot allocate more than the snapshot has
+        bincode::options()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(bytes.len() as u64)
+            .deserialize(bytes)
+            .map_err(|err| GenerationError::Snapshot(err.to_string()))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, GenerationError> {
+        serde_json::from_str(json).map_err(|err| GenerationError::Snapshot(err.to_string()))
+    }
+
+    pub fn restore(self) -> Result<Restored, GenerationError> {
+        let invalid = |message: &str| GenerationError::Snapshot(message.to_string());
+
+        if self.version != SNAPSHOT_VERSION {
+            return Err(GenerationError::Snapshot(format!(
+                "unsupported version {} (expected {})",
+                self.version, SNAPSHOT_VERSION
+            )));
+        }
+        if self.elevations.len() != self.sites.len()
+            || self.drainage_areas.len() != self.sites.len()
+            || self.population_densities.len() != self.sites.len()
+        {
+            return Err(invalid("the numbers of sites and values do not match"));
+        }
+        let [min_x, min_y] = self.bound_min;
+        let [max_x, max_y] = self.bound_max;
+        if !(min_x.is_finite() && min_y.is_finite() && max_x.is_finite() && max_y.is_finite())
+            || min_x >= max_x
+            || min_y >= max_y
+        {
+            return Err(invalid("the bounds are not finite"));
+        }
+        // NaN and infinite coordinates are outside too
+        let inside =
+            |[x, y]: [f64; 2]| (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y);
+        if !self.sites.iter().all(|&site| inside(site))
+            || !self.nodes.iter().all(|node| inside(node.site))
+            || !self
+                .rivers
+                .iter()
+                .all(|river| river.sites.iter().all(|&site| inside(site)))
+            || !inside(self.origin)
+            || !self
+                .settlements
+                .iter()
+                .all(|settlement| inside(settlement.site))
+            || !self.railway.iter().all(|&site| inside(site))
+            || !self
+                .districts
+                .iter()
+                .all(|district| inside(district.center))
+            || !self.pois.iter().all(|poi| inside(poi.site))
+        {
+            return Err(invalid("a site is out of the bounds"));
+        }
+        if !self.buildings.iter().all(|building| {
+            building
+                .corners
+                .iter()
+                .flatten()
+                .all(|value| value.is_finite())
+        }) {
+            return Err(invalid("a building is not finite"));
+        }
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(self.block_size) || !positive(self.address_road_distance_max) {
+            return Err(invalid("the sizes of the addresses are not positive"));
+        }
+
+        if self
+            .paths
+            .iter()
+            .any(|path| path.start >= self.nodes.len() || path.end >= self.nodes.len())
+        {
+            return Err(invalid("a path refers to a missing node"));
+        }
+        if self.paths.iter().any(|path| path.start == path.end) {
+            return Err(invalid("a path connects a node to itself"));
+        }
+        let mut connected = HashSet::with_capacity(self.paths.len());
+        if !self
+            .paths
+            .iter()
+            .all(|path| connected.insert((path.start.min(path.end), path.start.max(path.end))))
+        {
+            return Err(invalid("two paths connect the same nodes"));
+        }
+        if self.settlements.is_empty() {
+            return Err(invalid("no settlements"));
+        }
+        if self
+            .stations
+            .iter()
+            .any(|station| station.index >= self.railway.len())
+        {
+            return Err(invalid("a station is off the railway"));
+        }
+        if self
+            .streets
+            .iter()
+            .any(|street| street.nodes.iter().any(|&node| node >= self.nodes.len()))
+        {
+            return Err(invalid("a street refers to a missing node"));
+        }
+
+        let to_site2d = |[x, y]: [f64; 2]| Site2D { x, y };
+        let model = TerrainModel2DBulider::default()
+            .set_sites(self.sites.iter().copied().map(to_site2d).collect())
+            .set_bounding_box(
+                Some(to_site2d(self.bound_min)),
+                Some(to_site2d(self.bound_max)),
+            )
+            .build()?;
+        if model.sites().len() != self.sites.len() {
+            return Err(invalid("the sites cannot be triangulated"));
+        }
+        let terrain = model.create_terrain_from_result(&self.elevations);
+        let interpolator = Interpolator::new(terrain.sites());
+
+        let river_network = RiverNetwork::new(
+            self.drainage_areas,
+            self.rivers
+                .into_iter()
+                .map(|river| River {
+                    sites: river.sites.into_iter().map(to_site).collect(),
+                    order: river.order,
+                })
+                .collect(),
+        );
+
+        let network = RoadNetwork::new(
+            self.nodes
+                .iter()
+                .map(|node| TransportNode::new(to_site(node.site), Stage::new(node.stage)))
+                .collect(),
+            self.paths
+                .iter()
+                .map(|path| RoadPath {
+                    start: path.start,
+                    end: path.end,
+                    is_bridge: path.is_bridge,
+                })
+                .collect(),
+        );
+
+        let [city_name, county_name, subprefecture_name, subprefecture_postfix, government] =
+            self.nameset.map(Name::from_tuple);
+
+        let (settlements, settlement_names) = self
+            .settlements
+            .into_iter()
+            .map(|settlement| {
+                (
+                    Settlement {
+                        site: to_site(settlement.site),
+                        city_size_prop: settlement.city_size_prop,
+                        population: settlement.population,
+                    },
+                    Name::from_tuple(settlement.name),
+                )
+            })
+            .unzip();
+        let (stations, station_names) = self
+            .stations
+            .into_iter()
+            .map(|station| (station.index, Name::from_tuple(station.name)))
+            .unzip();
+        let railway = Railway {
+            sites: self.railway.into_iter().map(to_site).collect(),
+            stations,
+        };
+
+        let addressing = Addressing::new(
+            &network,
+            self.streets
+                .into_iter()
+                .map(|street| {
+                    Ok(NamedStreet {
+                        nodes: street.nodes,
+                        kind: StreetKind::from_code(street.kind)
+                            .ok_or_else(|| invalid("unknown kind of a street"))?,
+                        name: Name::from_tuple(street.name),
+                    })
+                })
+                .collect::<Result<_, GenerationError>>()?,
+            self.districts
+                .into_iter()
+                .map(|district| District {
+                    center: to_site(district.center),
+                    name: Name::from_tuple(district.name),
+                })
+                .collect(),
+            self.block_size,
+            self.address_road_distance_max,
+        );
+        let buildings = self
+            .buildings
+            .into_iter()
+            .map(|building| {
+                Building::new(
+                    building.corners.map(to_site),
+                    building.floors,
+                    building.height,
+                )
+            })
+            .collect();
+        let pois = self
+            .pois
+            .into_iter()
+            .map(|poi| {
+                Ok(Poi::new(
+                    PoiKind::from_code(poi.kind)
+                        .ok_or_else(|| invalid("unknown kind of a point of interest"))?,
+                    to_site(poi.site),
+                    Name::from_tuple(poi.name),
+                ))
+            })
+            .collect::<Result<_, GenerationError>>()?;
+
+        Ok(Restored {
+            map: Map::new(
+                terrain,
+                interpolator,
+                network,
+                river_network,
+                self.population_densities,
+                to_site(self.origin),
+                self.initial_angle,
+                self.population,
+                self.sea_level,
+                settlements,
+                railway,
+            ),
+            bound_min: to_site(self.bound_min),
+            bound_max: to_site(self.bound_max),
+            nameset: NameSet::new(
+                city_name,
+                county_name,
+                subprefecture_name,
+                subprefecture_postfix,
+                government,
+            ),
+            settlement_names,
+            station_names,
+            addressing,
+            buildings,
+            pois,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square of terrain with a road of two paths.
+    fn small_snapshot() -> Snapshot {
+        let name = || ("札幌".to_string(), "sapporo".to_string());
+        let node = |x, y| NodeSnapshot {
+            site: [x, y],
+            stage: 0,
+        };
+        let path = |start, end| PathSnapshot {
+            start,
+            end,
+            is_bridge: false,
+        };
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            bound_min: [0.0, 0.0],
+            bound_max: [10.0, 10.0],
+            sites: vec![[2.0, 2.0], [8.0, 2.0], [8.0, 8.0], [2.0, 8.0], [5.0, 5.0]],
+            elevations: vec![1.0; 5],
+            drainage_areas: vec![1.0; 5],
+            population_densities: vec![0.5; 5],
+            rivers: vec![RiverSnapshot {
+                sites: vec![[2.0, 8.0], [5.0, 5.0]],
+                order: 1,
+            }],
+            nodes: vec![node(3.0, 3.0), node(5.0, 3.0), node(7.0, 3.0)],
+            paths: vec![path(0, 1), path(1, 2)],
+            origin: [3.0, 3.0],
+            initial_angle: 0.0,
+            population: 100,
+            sea_level: 0.0,
+            nameset: [name(), name(), name(), name(), name()],
+            settlements: vec![SettlementSnapshot {
+                site: [3.0, 3.0],
+                city_size_prop: 1.0,
+                population: 100,
+                name: name(),
+            }],
+            railway: Vec::new(),
+            stations: Vec::new(),
+            streets: Vec::new(),
+            districts: Vec::new(),
+            block_size: 1.0,
+            address_road_distance_max: 1.0,
+            buildings: Vec::new(),
+            pois: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_restore_rejects_invalid() {
+        assert!(small_snapshot().restore().is_ok());
+
+        let is_invalid =
+            |snapshot: Snapshot| matches!(snapshot.restore(), Err(GenerationError::Snapshot(_)));
+        let mut snapshot = small_snapshot();
+        snapshot.nodes[1].site = [1e12, 3.0];
+        assert!(is_invalid(snapshot));
+        let mut snapshot = small_snapshot();
+        snapshot.nodes[1].site = [f64::NAN, 3.0];
+        assert!(is_invalid(snapshot));
+        let mut snapshot = small_snapshot();
+        snapshot.rivers[0].sites.push([5.0, f64::INFINITY]);
+        assert!(is_invalid(snapshot));
+        let mut snapshot = small_snapshot();
+        snapshot.bound_max = [f64::INFINITY, 10.0];
+        assert!(is_invalid(snapshot));
+        let mut snapshot = small_snapshot();
+        snapshot.paths[1].end = 1;
+        assert!(is_invalid(snapshot));
+        let mut snapshot = small_snapshot();
+        snapshot.paths[1] = PathSnapshot {
+            start: 1,
+            end: 0,
+            is_bridge: false,
+        };
+        assert!(is_invalid(snapshot));
+        let mut snapshot = small_snapshot();
+        snapshot.address_road_distance_max = f64::NAN;
+        assert!(is_invalid(snapshot));
+    }
+
+    #[test]
+    fn test_from_bytes_with_huge_length() {
+        // a valid header followed by the number of sites claiming far more than the bytes
+        let mut bytes = SNAPSHOT_VERSION.to_le_bytes().to_vec();
+        bytes.extend([0u8; 32]);
+        bytes.extend(u64::MAX.to_le_bytes());
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes),
+            Err(GenerationError::Snapshot(_))
+        ));
+    }
+}
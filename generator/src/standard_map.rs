@@ -12,153 +12,205 @@ use street_engine::{
 use wasm_bindgen::prelude::*;
 
 use crate::{
+    address::{Address, Addressing, Street},
+    block::{self, Block},
+    buffer::ElevationBuffer,
+    building::{self, Building},
+    coastline,
+    error::GenerationError,
+    export::{self, GeoProjection},
+    growth::RoadGrowth,
     map::{
-        generator::{MapConfig, MapGenerator},
+        generator::{MapConfig, MapGenerator, SettlementConfig},
+        railway::RailwayConfig,
+        random::RandomStream,
         terrain::TerrainConfig,
         Map,
     },
+    params::GenerationParams,
     placename::{NameConfig, NameGenerator},
-    types::{MapSite, Name, NameSet, NetworkNode, NetworkPath},
+    poi::{self, Poi},
+    progress::{CancellationToken, GenerationPhase, Progress},
+    snapshot::Snapshot,
+    types::{
+        to_map_sites, LandPolygon, MapSite, Name, NameSet, NetworkNode, NetworkPath, RiverPath,
+        Settlement, Station,
+    },
 };
 
 #[wasm_bindgen]
-pub fn create_standard_map(seed: u32, x_expand_prop: f64, dataset: String) -> Option<StandardMap> {
+pub fn create_standard_map(
+    seed: u32,
+    x_expand_prop: f64,
+    dataset: String,
+) -> Result<StandardMap, GenerationError> {
     StandardMap::new(seed, x_expand_prop, dataset)
 }
 
+/// Create a map, calling `callback(phase, fraction)` as generation proceeds.
+///
+/// `phase` is a string such as `"erosion"`, and `fraction` is `undefined` when unknown.
+/// Generation fails with the `cancelled` kind once `token` is cancelled.
+#[wasm_bindgen]
+pub fn create_standard_map_with_progress(
+    seed: u32,
+    x_expand_prop: f64,
+    dataset: String,
+    params: &GenerationParams,
+    callback: &js_sys::Function,
+    token: &CancellationToken,
+) -> Result<StandardMap, GenerationError> {
+    let progress = Progress::new(
+        |phase, fraction| {
+            // errors thrown by the callback do not stop generation
+            let _ = callback.call2(
+                &JsValue::NULL,
+                &phase.name().into(),
+                &fraction.map_or(JsValue::UNDEFINED, JsValue::from),
+            );
+        },
+        token.clone(),
+    );
+    StandardMap::with_progress(seed, x_expand_prop, dataset, params, &progress)
+}
+
+/// Start growing the roads of the map of the same arguments as [`StandardMap::with_params`].
+///
+/// The terrain is generated here, and the roads are grown by [`RoadGrowth::step`].
+#[wasm_bindgen]
+pub fn create_road_growth(
+    seed: u32,
+    x_expand_prop: f64,
+    params: &GenerationParams,
+) -> Result<RoadGrowth, GenerationError> {
+    let progress = Progress::default();
+    let mut rnd = StdRng::seed_from_u64(seed as u64);
+    let (terrain_config, map_config, _) =
+        StandardMap::configs(seed, x_expand_prop, params, &mut rnd)?;
+    Ok(RoadGrowth::new(StandardMap::map_generator(
+        terrain_config,
+        map_config,
+        &progress,
+    )?))
+}
+
+/// Rules of the roads of a standard map, boxed to keep the generator in a [`RoadGrowth`].
+pub(crate) type RulesFn =
+    Box<dyn Fn(f64, f64, Site, Angle, f64, Stage, Option<usize>) -> Option<TransportRules>>;
+
 #[wasm_bindgen]
 pub struct StandardMap {
     map: Map,
     bound_min: Site,
     bound_max: Site,
     nameset: NameSet,
+    /// Names of `map.settlements`, starting with the city name.
+    settlement_names: Vec<Name>,
+    /// Names of `map.railway.stations`.
+    station_names: Vec<Name>,
+    addressing: Addressing,
+    buildings: Vec<Building>,
+    pois: Vec<Poi>,
 }
 
 #[wasm_bindgen]
 impl StandardMap {
-    fn create_map(
+    fn map_generator(
         terrain_config: TerrainConfig,
         map_config: MapConfig,
-    ) -> Result<Map, Box<dyn std::error::Error>> {
-        let map = MapGenerator::new(
-            terrain_config.clone(),
-            map_config.clone(),
-            |elevation, population_density, site, angle, slope_rad, stage| {
-                Self::rules_fn(
-                    elevation,
-                    population_density,
-                    site,
-                    angle,
-                    slope_rad,
-                    stage,
-                    &map_config,
-                )
-            },
-        )?
-        .build()?;
-
-        Ok(map)
+        progress: &Progress,
+    ) -> Result<MapGenerator<RulesFn>, GenerationError> {
+        let rules_config = map_config.clone();
+        MapGenerator::new(
+            terrain_config,
+            map_config,
+            Box::new(
+                move |elevation,
+                      population_density,
+                      site,
+                      angle,
+                      slope_rad,
+                      stage,
+                      river_crossing| {
+                    Self::rules_fn(
+                        elevation,
+                        population_density,
+                        site,
+                        angle,
+                        slope_rad,
+                        stage,
+                        river_crossing,
+                        &rules_config,
+                    )
+                },
+            ),
+            progress,
+        )
     }
 
-    pub fn new(seed: u32, x_expand_prop: f64, dataset: String) -> Option<StandardMap> {
-        let mut rnd = StdRng::seed_from_u64(seed as u64);
-        let land_ratio = rnd.gen_range(0.5..0.9);
-        let erodibility_distribution_power = rnd.gen_range(3.0..5.0);
-        let city_size_prop_min = 0.01;
-        let city_size_prop_max = 0.12;
-        let city_size_prop = (city_size_prop_min
-            + (city_size_prop_max - city_size_prop_min) * rnd.gen::<f64>().powi(4))
-            * land_ratio;
-
-        println!("city_size_prop: {}", city_size_prop);
-        let mut namegen = NameGenerator::new(dataset.as_str(), seed as usize);
-        let city_name = Name::from_tuple(namegen.generate(NameConfig {
-            target_name_length: 3.1 - city_size_prop * 20.0,
-            cmp_samples: 5,
-        })?);
-
-        let map_config = MapConfig {
-            sea_level: 1e-3,
-            max_slope_livable: std::f64::consts::PI / 4.0,
-            origin_sample_num: 10,
-            max_retries: 500,
-            origin_min_evelation: 2.0,
-            city_size_prop,
-        };
+    pub fn new(
+        seed: u32,
+        x_expand_prop: f64,
+        dataset: String,
+    ) -> Result<StandardMap, GenerationError> {
+        Self::with_params(seed, x_expand_prop, dataset, &GenerationParams::default())
+    }
 
-        let bound = 250.0;
+    pub fn with_params(
+        seed: u32,
+        x_expand_prop: f64,
+        dataset: String,
+        params: &GenerationParams,
+    ) -> Result<StandardMap, GenerationError> {
+        Self::with_progress(seed, x_expand_prop, dataset, params, &Progress::default())
+    }
 
-        let terrain_config = TerrainConfig {
-            x_bound: bound * x_expand_prop,
-            y_bound: bound,
-            seed,
-            particle_num: 50000,
-            fault_scale: 0.15,
-            erodibility_distribution_power,
-            land_ratio,
-            convex_hull_is_always_outlet: false,
-            global_max_slope: None,
-        };
+    /// Serialize the map into a compact binary snapshot, which can be restored by [`Self::from_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.snapshot().to_bytes()
+    }
 
-        let map = Self::create_map(terrain_config.clone(), map_config.clone()).ok()?;
+    /// Restore a map from a binary snapshot without generating it again.
+    pub fn from_bytes(bytes: &[u8]) -> Result<StandardMap, GenerationError> {
+        Self::restore(Snapshot::from_bytes(bytes)?)
+    }
 
-        let gov_population = map.population + rnd.gen_range(0..map.population / 2);
-        let government = if gov_population < 3000 {
-            Name::from_tuple(("村".to_string(), "mura".to_string()))
-        } else if gov_population < 20000 {
-            Name::from_tuple(("町".to_string(), "cho".to_string()))
-        } else {
-            Name::from_tuple(("市".to_string(), "shi".to_string()))
-        };
-        let county_name_is_city_name = rnd.gen_bool(0.5) && (government.name() != "村");
-        let county_name = if county_name_is_city_name {
-            city_name.clone()
-        } else {
-            Name::from_tuple(namegen.generate(NameConfig {
-                target_name_length: 2.1,
-                cmp_samples: 5,
-            })?)
-        };
-        let subprefecture_name_is_city_name = (rnd.gen_bool(0.2) && government.name() == "市")
-            || (rnd.gen_bool(0.1) && government.name() == "町");
-        let subprefecture_name_is_county_name = rnd.gen_bool(0.1);
-        let subprefecture_name = if subprefecture_name_is_city_name {
-            city_name.clone()
-        } else if subprefecture_name_is_county_name {
-            county_name.clone()
-        } else {
-            Name::from_tuple(namegen.generate(NameConfig {
-                target_name_length: 2.1,
-                cmp_samples: 5,
-            })?)
-        };
+    /// Serialize the map into a JSON snapshot, which can be restored by [`Self::from_json`].
+    pub fn to_json(&self) -> String {
+        self.snapshot().to_json()
+    }
 
-        let subprefecture_postfix = if rnd.gen_bool(0.8) {
-            Name::from_tuple(("総合振興局".to_string(), "sogoshinkoukyoku".to_string()))
-        } else {
-            Name::from_tuple(("振興局".to_string(), "shinkoukyoku".to_string()))
-        };
+    /// Restore a map from a JSON snapshot without generating it again.
+    pub fn from_json(json: &str) -> Result<StandardMap, GenerationError> {
+        Self::restore(Snapshot::from_json(json)?)
+    }
 
-        let bound_min = terrain_config.bound_min();
-        let bound_max = terrain_config.bound_max();
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::new(
+            &self.map,
+            self.bound_min,
+            self.bound_max,
+            &self.nameset,
+            &self.settlement_names,
+            &self.station_names,
+            &self.addressing,
+            &self.buildings,
+            &self.pois,
+        )
+    }
 
-        Some(Self {
-            map,
-            bound_min: Site {
-                x: bound_min.x,
-                y: bound_min.y,
-            },
-            bound_max: Site {
-                x: bound_max.x,
-                y: bound_max.y,
-            },
-            nameset: NameSet::new(
-                city_name,
-                county_name,
-                subprefecture_name,
-                subprefecture_postfix,
-                government,
-            ),
+    fn restore(snapshot: Snapshot) -> Result<StandardMap, GenerationError> {
+        let restored = snapshot.restore()?;
+        Ok(Self {
+            map: restored.map,
+            bound_min: restored.bound_min,
+            bound_max: restored.bound_max,
+            nameset: restored.nameset,
+            settlement_names: restored.settlement_names,
+            station_names: restored.station_names,
+            addressing: restored.addressing,
+            buildings: restored.buildings,
+            pois: restored.pois,
         })
     }
 
@@ -166,10 +218,23 @@ impl StandardMap {
         self.nameset.clone()
     }
 
+    /// Total population of the settlements.
     pub fn get_population(&self) -> usize {
         self.map.population
     }
 
+    /// Settlements of the map, starting with the main town at the origin.
+    pub fn settlements(&self) -> Vec<Settlement> {
+        self.map
+            .settlements
+            .iter()
+            .zip(&self.settlement_names)
+            .map(|(settlement, name)| {
+                Settlement::new(settlement.site, name.clone(), settlement.population)
+            })
+            .collect()
+    }
+
     pub fn bound_min(&self) -> MapSite {
         MapSite {
             x: self.bound_min.x,
@@ -188,31 +253,131 @@ impl StandardMap {
         self.map.terrain.get_elevation(&Site2D { x, y })
     }
 
+    /// Nodes of the road network, indexed by their IDs.
+    ///
+    /// IDs are the indices of the nodes in the road network of the map, which is
+    /// the index-based copy of the generated `PathNetwork`. Unlike the IDs of
+    /// `PathNetwork`, they are saved in snapshots and stay the same after restoring.
+    pub fn network_nodes(&self) -> Vec<NetworkNode> {
+        self.map
+            .network
+            .nodes()
+            .iter()
+            .enumerate()
+            .map(|(id, &node)| NetworkNode::new(id, node))
+            .collect()
+    }
+
+    /// Node of the road network with the ID, if any.
+    pub fn network_node(&self, id: usize) -> Option<NetworkNode> {
+        let node = *self.map.network.nodes().get(id)?;
+        Some(NetworkNode::new(id, node))
+    }
+
+    /// IDs of the nodes connected to the node by a path.
+    pub fn network_neighbors(&self, id: usize) -> Vec<usize> {
+        self.map.network.neighbors(id).to_vec()
+    }
+
+    /// Number of paths at the node, e.g. 1 at a dead end and 3 or more at an intersection.
+    pub fn network_degree(&self, id: usize) -> usize {
+        self.map.network.neighbors(id).len()
+    }
+
     pub fn network_paths(&self) -> Vec<NetworkPath> {
+        let nodes = self.map.network.nodes();
         self.map
             .network
-            .nodes_iter()
-            .flat_map(|(inode_id, &inode)| {
-                let iter = self.map.network.neighbors_iter(inode_id);
-                if let Some(iter) = iter {
-                    iter.filter_map(|(jnode_id, &jnode)| {
-                        if inode_id < jnode_id {
-                            Some(NetworkPath::new(
-                                NetworkNode::new(inode),
-                                NetworkNode::new(jnode),
-                            ))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<NetworkPath>>()
-                } else {
-                    Vec::new()
-                }
+            .paths()
+            .iter()
+            .map(|path| {
+                NetworkPath::new(
+                    NetworkNode::new(path.start, nodes[path.start]),
+                    NetworkNode::new(path.end, nodes[path.end]),
+                    path.is_bridge,
+                )
             })
             .collect()
     }
 
+    /// Sites of the railway from west to east, or an empty list if the map has no railway.
+    pub fn railway_sites(&self) -> Vec<MapSite> {
+        to_map_sites(&self.map.railway.sites)
+    }
+
+    /// Stations on the railway from west to east.
+    pub fn stations(&self) -> Vec<Station> {
+        self.map
+            .railway
+            .stations
+            .iter()
+            .zip(&self.station_names)
+            .map(|(&index, name)| Station::new(self.map.railway.sites[index], name.clone()))
+            .collect()
+    }
+
+    /// Named streets: national and prefectural roads numbered from the longest main road,
+    /// and local roads named as 〜通 in the towns and 〜線 elsewhere.
+    pub fn streets(&self) -> Vec<Street> {
+        self.addressing.streets(&self.map)
+    }
+
+    /// Address of the given point (e.g. `中央北3条西2丁目`), or `None` in the sea or away from the roads.
+    pub fn address_at(&self, x: f64, y: f64) -> Option<Address> {
+        self.addressing.address_at(&self.map, x, y)
+    }
+
+    pub fn river_paths(&self) -> Vec<RiverPath> {
+        self.map
+            .river_network
+            .rivers
+            .iter()
+            .map(|river| RiverPath::new(river.sites.clone(), river.order))
+            .collect()
+    }
+
+    /// Population density at the given point, which drives the growth of the roads.
+    ///
+    /// The density is 1 at most around the main town, merged from all the settlements
+    /// and interpolated from the sites of the terrain. It is 0 outside the terrain.
+    pub fn get_population_density(&self, x: f64, y: f64) -> f64 {
+        self.map.population_density_at(x, y)
+    }
+
+    /// Area drained through the given point, interpolated from the flow routing of the terrain.
+    pub fn get_drainage_area(&self, x: f64, y: f64) -> Option<f64> {
+        self.map
+            .interpolator
+            .interpolate(
+                &self.map.river_network.drainage_areas,
+                naturalneighbor::Point { x, y },
+            )
+            .unwrap_or(None)
+    }
+
+    /// Land polygons enclosed by the coastline, with lakes as holes.
+    ///
+    /// The terrain is sampled on a `grid_width` x `grid_height` grid to trace the coastline.
+    pub fn coastline(&self, grid_width: u32, grid_height: u32) -> Vec<LandPolygon> {
+        let buffer = ElevationBuffer::from_terrain(self, grid_width, grid_height);
+        coastline::extract(&buffer, self.map.sea_level)
+    }
+
+    /// Blocks enclosed by the roads, with their area, perimeter and population density.
+    pub fn blocks(&self) -> Vec<Block> {
+        block::extract(&self.map)
+    }
+
+    /// Footprints of the buildings along the roads, taller in denser blocks.
+    pub fn buildings(&self) -> Vec<Building> {
+        self.buildings.clone()
+    }
+
+    /// Points of interest: the town hall, schools, post offices, shrines, fishing ports and 道の駅.
+    pub fn pois(&self) -> Vec<Poi> {
+        self.pois.clone()
+    }
+
     pub fn get_origin_site(&self) -> MapSite {
         MapSite {
             x: self.map.origin.x,
@@ -224,6 +389,14 @@ impl StandardMap {
         self.map.initial_angle
     }
 
+    /// Export the map as a GeoJSON FeatureCollection.
+    ///
+    /// If `projection` is not given, the map is placed around the center of Hokkaido.
+    pub fn to_geojson(&self, projection: Option<GeoProjection>) -> String {
+        export::feature_collection(self, &projection.unwrap_or_default()).to_string()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn rules_fn(
         elevation: f64,
         population_density: f64,
@@ -231,12 +404,20 @@ impl StandardMap {
         _: Angle,
         slope_rad: f64,
         stage: Stage,
+        river_crossing: Option<usize>,
         map_config: &MapConfig,
     ) -> Option<TransportRules> {
         if elevation < map_config.sea_level {
             return None;
         }
 
+        // main roads may bridge any river, while local roads only cross small streams
+        let bridge_penalty = match river_crossing {
+            None => 0.0,
+            Some(order) if stage.as_num() > 0 && order > 2 => return None,
+            Some(order) => order as f64 * 0.5,
+        };
+
         let population_density = if stage.as_num() > 0 {
             population_density
         } else {
@@ -244,14 +425,15 @@ impl StandardMap {
         };
 
         let slope_prop = slope_rad.abs() / (PI * 0.5);
-        let path_priority = (1e-9 + population_density) * (-elevation - slope_prop);
+        let path_priority =
+            (1e-9 + population_density) * (-elevation - slope_prop) - bridge_penalty;
 
         if stage.as_num() > 0 {
             Some(TransportRules {
                 path_priority,
                 elevation,
                 population_density,
-                path_normal_length: 0.5,
+                path_normal_length: map_config.path_normal_length,
                 path_extra_length_for_intersection: 0.3,
                 branch_rules: BranchRules {
                     branch_density: 0.01
@@ -272,17 +454,689 @@ impl StandardMap {
                 path_priority: path_priority + 1e5,
                 elevation,
                 population_density,
-                path_normal_length: 0.5,
+                path_normal_length: map_config.path_normal_length,
                 path_extra_length_for_intersection: 0.3,
                 branch_rules: BranchRules {
                     branch_density: 0.05 + population_density * 0.55,
                     staging_probability: 0.99 - (0.4 - slope_prop).max(0.0) * 0.1,
                 },
                 path_direction_rules: PathDirectionRules {
-                    max_radian: std::f64::consts::PI / (40.0 + 8000.0 * (0.4 - slope_prop).max(0.0)),
+                    max_radian: std::f64::consts::PI
+                        / (40.0 + 8000.0 * (0.4 - slope_prop).max(0.0)),
                     comparison_step: 5,
                 },
             })
         }
     }
 }
+
+impl StandardMap {
+    pub(crate) fn sea_level(&self) -> f64 {
+        self.map.sea_level
+    }
+
+    /// Configs of the terrain and the map, with the size of the main town.
+    ///
+    /// `rnd` is the generator seeded by `seed`, which is used for the names afterwards.
+    fn configs(
+        seed: u32,
+        x_expand_prop: f64,
+        params: &GenerationParams,
+        rnd: &mut StdRng,
+    ) -> Result<(TerrainConfig, MapConfig, f64), GenerationError> {
+        params.validate()?;
+        if !(x_expand_prop.is_finite() && x_expand_prop > 0.0) {
+            return Err(GenerationError::Params(format!(
+                "`x_expand_prop` must be positive, but is {}",
+                x_expand_prop
+            )));
+        }
+        let mut gen_range = |min: f64, max: f64| {
+            if min < max {
+                rnd.gen_range(min..max)
+            } else {
+                min
+            }
+        };
+        let land_ratio = gen_range(params.land_ratio_min, params.land_ratio_max);
+        let erodibility_distribution_power = gen_range(
+            params.erodibility_distribution_power_min,
+            params.erodibility_distribution_power_max,
+        );
+        let city_size_prop = (params.city_size_prop_min
+            + (params.city_size_prop_max - params.city_size_prop_min)
+                * rnd.gen::<f64>().powi(params.city_size_power))
+            * land_ratio;
+
+        let mut sizes = RandomStream::SettlementSizes.rng(seed);
+        let main_town = SettlementConfig {
+            origin: params
+                .origin_x
+                .zip(params.origin_y)
+                .map(|(x, y)| Site { x, y }),
+            city_size_prop,
+            weight: 1.0,
+        };
+        let satellites = (0..params.satellite_num).map(|_| {
+            let size_ratio = if params.satellite_size_ratio_min < params.satellite_size_ratio_max {
+                sizes.gen_range(params.satellite_size_ratio_min..params.satellite_size_ratio_max)
+            } else {
+                params.satellite_size_ratio_min
+            };
+            SettlementConfig {
+                origin: None,
+                city_size_prop: city_size_prop * size_ratio,
+                weight: params.satellite_weight,
+            }
+        });
+
+        let map_config = MapConfig {
+            sea_level: params.sea_level,
+            max_slope_livable: params.max_slope_livable,
+            origin_sample_num: params.origin_sample_num,
+            max_retries: params.max_retries,
+            origin_min_evelation: params.origin_min_elevation,
+            river_drainage_area_min: params.river_drainage_area_min,
+            path_normal_length: params.path_normal_length,
+            settlements: std::iter::once(main_town).chain(satellites).collect(),
+            settlement_distance_min: params.settlement_distance_min,
+            highway_slope_penalty: params.highway_slope_penalty,
+            initial_angle: params.initial_angle,
+            railway: params.railway.then_some(RailwayConfig {
+                max_slope: params.railway_max_slope,
+                slope_penalty: params.railway_slope_penalty,
+                station_distance_min: params.station_distance_min,
+                station_density_min: params.station_density_min,
+            }),
+        };
+
+        let terrain_config = TerrainConfig {
+            x_bound: params.bound * x_expand_prop,
+            y_bound: params.bound,
+            seed,
+            particle_num: params.particle_num,
+            fault_scale: params.fault_scale,
+            erodibility_distribution_power,
+            land_ratio,
+            convex_hull_is_always_outlet: params.convex_hull_is_always_outlet,
+            global_max_slope: params.global_max_slope,
+        };
+
+        Ok((terrain_config, map_config, city_size_prop))
+    }
+
+    /// Same as [`Self::with_params`], reporting the progress and stopping when cancelled.
+    pub fn with_progress(
+        seed: u32,
+        x_expand_prop: f64,
+        dataset: String,
+        params: &GenerationParams,
+        progress: &Progress,
+    ) -> Result<StandardMap, GenerationError> {
+        let mut rnd = StdRng::seed_from_u64(seed as u64);
+        let (terrain_config, map_config, city_size_prop) =
+            Self::configs(seed, x_expand_prop, params, &mut rnd)?;
+
+        let map =
+            Self::map_generator(terrain_config.clone(), map_config, progress)?.build(progress)?;
+
+        progress.report(GenerationPhase::Naming, Some(0.0))?;
+        let mut namegen = NameGenerator::new(dataset.as_str(), seed as usize);
+        let city_name = Name::from_tuple(namegen.generate(NameConfig {
+            target_name_length: params.city_name_length
+                - city_size_prop * params.city_name_length_decay,
+            cmp_samples: params.name_cmp_samples,
+        })?);
+
+        let gov_population = map.population + rnd.gen_range(0..(map.population / 2).max(1));
+        let government = if gov_population < params.village_population_max {
+            Name::from_tuple(("村".to_string(), "mura".to_string()))
+        } else if gov_population < params.town_population_max {
+            Name::from_tuple(("町".to_string(), "cho".to_string()))
+        } else {
+            Name::from_tuple(("市".to_string(), "shi".to_string()))
+        };
+        let county_name_is_city_name = rnd.gen_bool(0.5) && (government.name() != "村");
+        let county_name = if county_name_is_city_name {
+            city_name.clone()
+        } else {
+            Name::from_tuple(namegen.generate(NameConfig {
+                target_name_length: params.county_name_length,
+                cmp_samples: params.name_cmp_samples,
+            })?)
+        };
+        let subprefecture_name_is_city_name = (rnd.gen_bool(0.2) && government.name() == "市")
+            || (rnd.gen_bool(0.1) && government.name() == "町");
+        let subprefecture_name_is_county_name = rnd.gen_bool(0.1);
+        let subprefecture_name = if subprefecture_name_is_city_name {
+            city_name.clone()
+        } else if subprefecture_name_is_county_name {
+            county_name.clone()
+        } else {
+            Name::from_tuple(namegen.generate(NameConfig {
+                target_name_length: params.subprefecture_name_length,
+                cmp_samples: params.name_cmp_samples,
+            })?)
+        };
+
+        let subprefecture_postfix = if rnd.gen_bool(0.8) {
+            Name::from_tuple(("総合振興局".to_string(), "sogoshinkoukyoku".to_string()))
+        } else {
+            Name::from_tuple(("振興局".to_string(), "shinkoukyoku".to_string()))
+        };
+
+        // satellites are named last so that the other names do not depend on them
+        let mut settlement_names = vec![city_name.clone()];
+        for settlement in &map.settlements[1..] {
+            settlement_names.push(Name::from_tuple(namegen.generate(NameConfig {
+                target_name_length: params.city_name_length
+                    - settlement.city_size_prop * params.city_name_length_decay,
+                cmp_samples: params.name_cmp_samples,
+            })?));
+        }
+
+        // a station near a settlement takes its name
+        let mut named = vec![false; map.settlements.len()];
+        let mut station_names = Vec::with_capacity(map.railway.stations.len());
+        for &index in &map.railway.stations {
+            let site = map.railway.sites[index];
+            let settlement = (0..map.settlements.len())
+                .filter(|&i| !named[i])
+                .map(|i| (i, map.settlements[i].site.distance(&site)))
+                .filter(|&(_, distance)| distance < params.station_distance_min * 0.5)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let name = match settlement {
+                Some((i, _)) => {
+                    named[i] = true;
+                    settlement_names[i].clone()
+                }
+                None => Name::from_tuple(namegen.generate(NameConfig {
+                    target_name_length: params.station_name_length,
+                    cmp_samples: params.name_cmp_samples,
+                })?),
+            };
+            station_names.push(name);
+        }
+
+        let addressing = Addressing::generate(&map, &settlement_names, &mut namegen, params, seed);
+
+        let buildings = if params.buildings {
+            building::generate(&map, &block::extract(&map), params, seed)
+        } else {
+            Vec::new()
+        };
+
+        let nameset = NameSet::new(
+            city_name,
+            county_name,
+            subprefecture_name,
+            subprefecture_postfix,
+            government,
+        );
+        let pois = poi::generate(&map, &nameset, &mut namegen, params, seed);
+
+        progress.report(GenerationPhase::Naming, Some(1.0))?;
+
+        let bound_min = terrain_config.bound_min();
+        let bound_max = terrain_config.bound_max();
+
+        Ok(Self {
+            map,
+            bound_min: Site {
+                x: bound_min.x,
+                y: bound_min.y,
+            },
+            bound_max: Site {
+                x: bound_max.x,
+                y: bound_max.y,
+            },
+            nameset,
+            settlement_names,
+            station_names,
+            addressing,
+            buildings,
+            pois,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use street_engine::core::geometry::line_segment::LineSegment;
+
+    use super::*;
+    use crate::{address::StreetKind, poi::PoiKind};
+
+    const DATASET: &str = include_str!("../../frontend/static/dataset/placenames.csv");
+
+    /// Parameters of a small map, which is fast enough to generate in tests.
+    fn small_params() -> GenerationParams {
+        GenerationParams {
+            bound: 100.0,
+            particle_num: 2000,
+            ..Default::default()
+        }
+    }
+
+    /// Small map of the seed 1, generated once and shared by the tests.
+    fn small_map() -> &'static StandardMap {
+        static MAP: OnceLock<StandardMap> = OnceLock::new();
+        MAP.get_or_init(|| {
+            StandardMap::with_params(1, 1.4, DATASET.into(), &small_params()).unwrap()
+        })
+    }
+
+    /// FNV-1a, which is stable across platforms and Rust versions unlike `DefaultHasher`.
+    struct Fnv1a(u64);
+
+    impl Fnv1a {
+        fn new() -> Self {
+            Self(0xcbf29ce484222325)
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            bytes.iter().for_each(|&byte| {
+                self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+            });
+        }
+
+        fn write_f64(&mut self, value: f64) {
+            self.write(&value.to_bits().to_le_bytes());
+        }
+
+        fn write_usize(&mut self, value: usize) {
+            self.write(&(value as u64).to_le_bytes());
+        }
+    }
+
+    /// Hashes of the terrain, the road network and the names.
+    fn hashes(standard: &StandardMap) -> [u64; 3] {
+        let mut terrain = Fnv1a::new();
+        standard
+            .map
+            .terrain
+            .sites()
+            .iter()
+            .zip(standard.map.terrain.elevations())
+            .for_each(|(site, &elevation)| {
+                terrain.write_f64(site.x);
+                terrain.write_f64(site.y);
+                terrain.write_f64(elevation);
+            });
+
+        let mut network = Fnv1a::new();
+        standard.map.network.nodes().iter().for_each(|node| {
+            network.write_f64(node.site.x);
+            network.write_f64(node.site.y);
+            network.write_usize(node.stage.as_num());
+        });
+        standard.map.network.paths().iter().for_each(|path| {
+            network.write_usize(path.start);
+            network.write_usize(path.end);
+            network.write(&[path.is_bridge as u8]);
+        });
+
+        let mut nameset = Fnv1a::new();
+        let names = &standard.nameset;
+        [
+            names.city_name(),
+            names.county_name(),
+            names.subprefecture_name(),
+            names.subprefecture_postfix(),
+            names.government(),
+        ]
+        .iter()
+        .for_each(|name| {
+            nameset.write(name.name().as_bytes());
+            nameset.write(&[0]);
+            nameset.write(name.reading().as_bytes());
+            nameset.write(&[0]);
+        });
+
+        [terrain.0, network.0, nameset.0]
+    }
+
+    #[test]
+    fn test_golden_seeds() {
+        let params = small_params();
+
+        // update these only when the change of the generated maps is intended
+        let golden: [(u32, [u64; 3]); 3] = [
+            (
+                1,
+                [0xe76e6fe785f9b71a, 0x758717e35cc7c664, 0x4317b7fb0c962879],
+            ),
+            (
+                545903,
+                [0x8d93d7822c6ea0cb, 0x1632043c8c76769c, 0xf9b14b65184c6ecf],
+            ),
+            (
+                4242,
+                [0xd5b04061f47a7bfc, 0x02008d2d26db0100, 0xacf6af309ea9d2ab],
+            ),
+        ];
+        for (seed, expected) in golden {
+            let standard = StandardMap::with_params(seed, 1.4, DATASET.into(), &params).unwrap();
+            assert_eq!(hashes(&standard), expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_origin_override() {
+        let params = small_params();
+        let origin = small_map().get_origin_site();
+
+        let params = GenerationParams {
+            origin_x: Some(origin.x + 1.0),
+            origin_y: Some(origin.y),
+            initial_angle: Some(1.0),
+            ..params
+        };
+        let pinned = StandardMap::with_params(1, 1.4, DATASET.into(), &params).unwrap();
+        assert_eq!(pinned.get_origin_site().x, origin.x + 1.0);
+        assert_eq!(pinned.get_origin_site().y, origin.y);
+        assert_eq!(pinned.get_initial_angle(), 1.0);
+
+        let params = GenerationParams {
+            origin_x: Some(1e6),
+            ..params
+        };
+        assert!(matches!(
+            StandardMap::with_params(1, 1.4, DATASET.into(), &params),
+            Err(GenerationError::InvalidOrigin)
+        ));
+    }
+
+    #[test]
+    fn test_settlements() {
+        let params = GenerationParams {
+            satellite_num: 3,
+            settlement_distance_min: 15.0,
+            ..small_params()
+        };
+        let standard = StandardMap::with_params(1, 1.4, DATASET.into(), &params).unwrap();
+
+        let settlements = standard.settlements();
+        assert!(settlements.len() > 1);
+        assert_eq!(settlements[0].site().x, standard.get_origin_site().x);
+        assert_eq!(
+            settlements[0].name().name(),
+            standard.get_nameset().city_name().name()
+        );
+        assert_eq!(
+            settlements
+                .iter()
+                .map(|settlement| settlement.population())
+                .sum::<usize>(),
+            standard.get_population()
+        );
+
+        // the origins of the settlements are the first nodes, connected by roads or highways
+        let components = standard.map.network.components();
+        assert!((1..settlements.len()).all(|i| components[i] == components[0]));
+
+        // the highways are split where they cross the other roads
+        let paths = standard.network_paths();
+        let segment = |path: &NetworkPath| {
+            let (a, b) = (path.node1().site(), path.node2().site());
+            LineSegment::new(Site { x: a.x, y: a.y }, Site { x: b.x, y: b.y })
+        };
+        for (i, a) in paths.iter().enumerate() {
+            let ids = [a.node1_id(), a.node2_id()];
+            assert!(paths[..i]
+                .iter()
+                .filter(|b| !ids.contains(&b.node1_id()) && !ids.contains(&b.node2_id()))
+                .all(|b| segment(a).get_intersection(&segment(b)).is_none()));
+        }
+    }
+
+    #[test]
+    fn test_railway() {
+        let params = small_params();
+        let standard = small_map();
+
+        let railway = standard.railway_sites();
+        let stations = standard.stations();
+        assert!(railway.len() > 1);
+        assert!(!stations.is_empty());
+        assert!(stations.iter().all(|station| railway
+            .iter()
+            .any(|site| site.x == station.site().x && site.y == station.site().y)));
+
+        let params = GenerationParams {
+            railway: false,
+            ..params
+        };
+        let standard = StandardMap::with_params(1, 1.4, DATASET.into(), &params).unwrap();
+        assert!(standard.railway_sites().is_empty());
+        assert!(standard.stations().is_empty());
+    }
+
+    #[test]
+    fn test_network_graph() {
+        let standard = small_map();
+
+        let nodes = standard.network_nodes();
+        let paths = standard.network_paths();
+        assert!(!paths.is_empty());
+        assert!(nodes.iter().enumerate().all(|(i, node)| node.id() == i));
+        assert!(standard.network_node(nodes.len()).is_none());
+        for path in &paths {
+            let (id1, id2) = (path.node1_id(), path.node2_id());
+            let site = nodes[id1].site();
+            assert_eq!(
+                (site.x, site.y),
+                (path.node1().site().x, path.node1().site().y)
+            );
+            assert_eq!(path.stage(), nodes[id1].stage().max(nodes[id2].stage()));
+            assert!(standard.network_neighbors(id1).contains(&id2));
+            assert!(standard.network_neighbors(id2).contains(&id1));
+        }
+        assert_eq!(
+            (0..nodes.len())
+                .map(|id| standard.network_degree(id))
+                .sum::<usize>(),
+            paths.len() * 2
+        );
+
+        let restored = StandardMap::from_bytes(&standard.to_bytes()).unwrap();
+        assert!((0..nodes.len())
+            .all(|id| restored.network_neighbors(id) == standard.network_neighbors(id)));
+    }
+
+    #[test]
+    fn test_addresses() {
+        let params = small_params();
+        let standard = small_map();
+
+        let streets = standard.streets();
+        assert!(streets
+            .iter()
+            .any(|street| street.kind() == StreetKind::National));
+        assert!(streets.iter().all(|street| street.sites().len() > 1));
+
+        let origin = standard.get_origin_site();
+        let address = standard.address_at(origin.x, origin.y).unwrap();
+        assert_eq!(address.district().name(), "中央");
+        assert_eq!((address.jo(), address.chome()), (1, 1));
+        assert!(address.text().starts_with("中央"));
+        assert!(standard
+            .address_at(params.bound * 10.0, params.bound * 10.0)
+            .is_none());
+
+        let restored = StandardMap::from_bytes(&standard.to_bytes()).unwrap();
+        let restored_address = restored.address_at(origin.x, origin.y).unwrap();
+        assert_eq!(restored_address.text(), address.text());
+        assert_eq!(restored.streets().len(), streets.len());
+
+        // the names are numbered when they fail to generate
+        let failing = GenerationParams {
+            name_cmp_samples: 0,
+            ..params
+        };
+        let mut namegen = NameGenerator::new(DATASET, 1);
+        let addressing = Addressing::generate(
+            &standard.map,
+            &standard.settlement_names,
+            &mut namegen,
+            &failing,
+            1,
+        );
+        assert_eq!(addressing.streets.len(), standard.addressing.streets.len());
+        assert!(addressing.districts.len() > standard.settlement_names.len());
+        assert!(addressing.districts[standard.settlement_names.len()..]
+            .iter()
+            .all(|district| district.name.name().starts_with('第')));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let standard = small_map();
+
+        let blocks = standard.blocks();
+        assert!(!blocks.is_empty());
+        assert!(blocks.iter().all(|block| {
+            let sites = block.sites();
+            block.area() > 0.0
+                && block.perimeter() > 0.0
+                && (0.0..=1.0).contains(&block.population_density())
+                && sites.first().map(|site| (site.x, site.y))
+                    == sites.last().map(|site| (site.x, site.y))
+        }));
+        let area = blocks.iter().map(|block| block.area()).sum::<f64>();
+        let (bound_min, bound_max) = (standard.bound_min(), standard.bound_max());
+        assert!(area < (bound_max.x - bound_min.x) * (bound_max.y - bound_min.y));
+    }
+
+    #[test]
+    fn test_buildings() {
+        let params = small_params();
+        let standard = small_map();
+
+        let buildings = standard.buildings();
+        assert!(!buildings.is_empty());
+        assert!(buildings.iter().all(|building| {
+            building.sites().len() == 5
+                && (1..=params.building_floors_max).contains(&building.floors())
+                && building.height() > 0.0
+        }));
+        let geojson: serde_json::Value = serde_json::from_str(&standard.to_geojson(None)).unwrap();
+        assert_eq!(
+            geojson["features"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|feature| feature["properties"]["kind"] == "building")
+                .count(),
+            buildings.len()
+        );
+
+        let params = GenerationParams {
+            buildings: false,
+            ..params
+        };
+        let standard = StandardMap::with_params(1, 1.4, DATASET.into(), &params).unwrap();
+        assert!(standard.buildings().is_empty());
+    }
+
+    #[test]
+    fn test_pois() {
+        let params = small_params();
+        let standard = small_map();
+
+        let pois = standard.pois();
+        let of_kind = |kind| {
+            pois.iter()
+                .filter(|poi| poi.kind() == kind)
+                .collect::<Vec<_>>()
+        };
+        let town_halls = of_kind(PoiKind::TownHall);
+        assert_eq!(town_halls.len(), 1);
+        let nameset = standard.get_nameset();
+        assert!(town_halls[0].name().name().starts_with(&format!(
+            "{}{}",
+            nameset.city_name().name(),
+            nameset.government().name()
+        )));
+        assert!(!of_kind(PoiKind::School).is_empty());
+        assert!(!of_kind(PoiKind::PostOffice).is_empty());
+        assert!(!of_kind(PoiKind::Shrine).is_empty());
+        assert!(of_kind(PoiKind::FishingPort).len() <= params.fishing_port_num_max);
+        assert!(of_kind(PoiKind::RoadStation).iter().all(|poi| {
+            let site = poi.site();
+            standard.settlements().iter().all(|settlement| {
+                let other = settlement.site();
+                (site.x - other.x).hypot(site.y - other.y) >= params.road_station_distance_min
+            })
+        }));
+
+        let restored = StandardMap::from_bytes(&standard.to_bytes()).unwrap();
+        assert_eq!(
+            restored
+                .pois()
+                .iter()
+                .map(|poi| poi.name().name())
+                .collect::<Vec<_>>(),
+            pois.iter().map(|poi| poi.name().name()).collect::<Vec<_>>()
+        );
+
+        // the names are numbered when they fail to generate
+        let failing = GenerationParams {
+            name_cmp_samples: 0,
+            ..params
+        };
+        let mut namegen = NameGenerator::new(DATASET, 1);
+        let numbered = poi::generate(&standard.map, &nameset, &mut namegen, &failing, 1);
+        assert_eq!(numbered.len(), pois.len());
+        assert!(numbered
+            .iter()
+            .filter(|poi| poi.kind() != PoiKind::TownHall)
+            .all(|poi| poi.name().name().contains('第')));
+    }
+
+    #[test]
+    fn test_progress() {
+        let params = small_params();
+
+        let phases = std::cell::RefCell::new(Vec::new());
+        let progress = Progress::new(
+            |phase, _| {
+                let mut phases = phases.borrow_mut();
+                if phases.last() != Some(&phase) {
+                    phases.push(phase);
+                }
+            },
+            CancellationToken::new(),
+        );
+        StandardMap::with_progress(1, 1.4, DATASET.into(), &params, &progress).unwrap();
+        assert_eq!(
+            *phases.borrow(),
+            vec![
+                GenerationPhase::SiteRelaxation,
+                GenerationPhase::OutletDetermination,
+                GenerationPhase::Erosion,
+                GenerationPhase::PopulationDensity,
+                GenerationPhase::RiverExtraction,
+                GenerationPhase::RoadIteration,
+                GenerationPhase::Railway,
+                GenerationPhase::Naming,
+            ]
+        );
+
+        let token = CancellationToken::new();
+        let progress = Progress::new(
+            |phase, _| {
+                if phase == GenerationPhase::RoadIteration {
+                    token.cancel();
+                }
+            },
+            token.clone(),
+        );
+        assert!(matches!(
+            StandardMap::with_progress(1, 1.4, DATASET.into(), &params, &progress),
+            Err(GenerationError::Cancelled)
+        ));
+    }
+}
@@ -1,6 +1,8 @@
-use street_engine::transport::node::TransportNode;
+use street_engine::{core::geometry::site::Site, transport::node::TransportNode};
 use wasm_bindgen::prelude::*;
 
+use crate::isoline::signed_area;
+
 #[wasm_bindgen]
 #[derive(Debug, Clone)]
 
@@ -74,6 +76,67 @@ impl NameSet {
     }
 }
 
+/// A town or a village of the map.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Settlement {
+    site: Site,
+    name: Name,
+    population: usize,
+}
+
+#[wasm_bindgen]
+impl Settlement {
+    pub(crate) fn new(site: Site, name: Name, population: usize) -> Self {
+        Self {
+            site,
+            name,
+            population,
+        }
+    }
+
+    pub fn site(&self) -> MapSite {
+        MapSite {
+            x: self.site.x,
+            y: self.site.y,
+        }
+    }
+
+    pub fn name(&self) -> Name {
+        self.name.clone()
+    }
+
+    pub fn population(&self) -> usize {
+        self.population
+    }
+}
+
+/// A railway station. The station is called `{name}駅`.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Station {
+    site: Site,
+    name: Name,
+}
+
+#[wasm_bindgen]
+impl Station {
+    pub(crate) fn new(site: Site, name: Name) -> Self {
+        Self { site, name }
+    }
+
+    pub fn site(&self) -> MapSite {
+        MapSite {
+            x: self.site.x,
+            y: self.site.y,
+        }
+    }
+
+    pub fn name(&self) -> Name {
+        self.name.clone()
+    }
+}
+
 #[wasm_bindgen]
 pub struct MapSite {
     pub x: f64,
@@ -83,13 +146,20 @@ pub struct MapSite {
 #[wasm_bindgen]
 #[derive(Debug, Clone)]
 pub struct NetworkNode {
+    id: usize,
     transport_node: TransportNode,
 }
 
 #[wasm_bindgen]
 impl NetworkNode {
-    pub(crate) fn new(transport_node: TransportNode) -> Self {
-        Self { transport_node }
+    pub(crate) fn new(id: usize, transport_node: TransportNode) -> Self {
+        Self { id, transport_node }
+    }
+
+    /// Index of the node in the road network, which is in the order of creation
+    /// and stays the same in snapshots.
+    pub fn id(&self) -> usize {
+        self.id
     }
 
     pub fn site(&self) -> MapSite {
@@ -99,7 +169,8 @@ impl NetworkNode {
         }
     }
 
-    fn stage(&self) -> usize {
+    /// Stage of the node, where 0 is a main road.
+    pub fn stage(&self) -> usize {
         self.transport_node.stage.as_num()
     }
 }
@@ -109,12 +180,17 @@ impl NetworkNode {
 pub struct NetworkPath {
     node1: NetworkNode,
     node2: NetworkNode,
+    is_bridge: bool,
 }
 
 #[wasm_bindgen]
 impl NetworkPath {
-    pub(crate) fn new(node1: NetworkNode, node2: NetworkNode) -> Self {
-        Self { node1, node2 }
+    pub(crate) fn new(node1: NetworkNode, node2: NetworkNode, is_bridge: bool) -> Self {
+        Self {
+            node1,
+            node2,
+            is_bridge,
+        }
     }
 
     pub fn node1(&self) -> NetworkNode {
@@ -125,7 +201,132 @@ impl NetworkPath {
         self.node2.clone()
     }
 
+    pub fn node1_id(&self) -> usize {
+        self.node1.id
+    }
+
+    pub fn node2_id(&self) -> usize {
+        self.node2.id
+    }
+
     pub fn stage(&self) -> usize {
         self.node1.stage().max(self.node2.stage())
     }
+
+    /// Whether the path crosses a river.
+    pub fn is_bridge(&self) -> bool {
+        self.is_bridge
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct RiverPath {
+    sites: Vec<Site>,
+    order: usize,
+}
+
+#[wasm_bindgen]
+impl RiverPath {
+    pub(crate) fn new(sites: Vec<Site>, order: usize) -> Self {
+        Self { sites, order }
+    }
+
+    /// Sites of the river from upstream to downstream.
+    pub fn sites(&self) -> Vec<MapSite> {
+        to_map_sites(&self.sites)
+    }
+
+    /// Strahler order of the river.
+    pub fn order(&self) -> usize {
+        self.order
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct LandPolygon {
+    exterior: Vec<Site>,
+    holes: Vec<Vec<Site>>,
+}
+
+#[wasm_bindgen]
+impl LandPolygon {
+    pub(crate) fn new(exterior: Vec<Site>, holes: Vec<Vec<Site>>) -> Self {
+        Self { exterior, holes }
+    }
+
+    /// Closed ring of the coastline (the first and the last sites are the same).
+    pub fn exterior(&self) -> Vec<MapSite> {
+        to_map_sites(&self.exterior)
+    }
+
+    pub fn holes_len(&self) -> usize {
+        self.holes.len()
+    }
+
+    /// Closed ring of the `index`-th lake in the land.
+    pub fn hole(&self, index: usize) -> Vec<MapSite> {
+        self.holes
+            .get(index)
+            .map(|hole| to_map_sites(hole))
+            .unwrap_or_default()
+    }
+
+    /// Area of the land excluding lakes.
+    pub fn area(&self) -> f64 {
+        let ring_area = |ring: &[Site]| {
+            signed_area(&ring.iter().map(|site| (site.x, site.y)).collect::<Vec<_>>()).abs()
+        };
+        ring_area(&self.exterior) - self.holes.iter().map(|hole| ring_area(hole)).sum::<f64>()
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct ContourLine {
+    level: f64,
+    is_index: bool,
+    is_closed: bool,
+    sites: Vec<Site>,
+}
+
+#[wasm_bindgen]
+impl ContourLine {
+    pub(crate) fn new(level: f64, is_index: bool, is_closed: bool, sites: Vec<Site>) -> Self {
+        Self {
+            level,
+            is_index,
+            is_closed,
+            sites,
+        }
+    }
+
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Whether the line is an index contour (drawn thicker on topographic maps).
+    pub fn is_index(&self) -> bool {
+        self.is_index
+    }
+
+    /// Whether the line is a closed ring. Open lines end at the edge of the map.
+    pub fn is_closed(&self) -> bool {
+        self.is_closed
+    }
+
+    pub fn sites(&self) -> Vec<MapSite> {
+        to_map_sites(&self.sites)
+    }
+}
+
+pub(crate) fn to_map_sites(sites: &[Site]) -> Vec<MapSite> {
+    sites
+        .iter()
+        .map(|site| MapSite {
+            x: site.x,
+            y: site.y,
+        })
+        .collect()
 }